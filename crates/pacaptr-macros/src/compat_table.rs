@@ -14,9 +14,10 @@ const PM_IMPL_DIR: &str = "src/pm/";
 
 // We have to specify the length there (the elision is blocked by https://github.com/rust-lang/rfcs/pull/2545).
 // TODO: Fix this when the issue is resolved.
-const METHODS: [&str; 31] = [
-    "q", "qc", "qe", "qi", "qii", "qk", "ql", "qm", "qo", "qp", "qs", "qu", "r", "rn", "rns", "rs",
-    "rss", "s", "sc", "scc", "sccc", "sg", "si", "sii", "sl", "ss", "su", "suy", "sw", "sy", "u",
+const METHODS: [&str; 44] = [
+    "dd", "de", "f", "fl", "fx", "fy", "q", "qc", "qd", "qdt", "qe", "qh", "qi", "qii", "qk", "ql",
+    "qm", "qo", "qp", "qs", "qt", "qu", "r", "rn", "rns", "rs", "rss", "s", "sas", "sau", "sc",
+    "scc", "sccc", "sg", "si", "sii", "sl", "ss", "su", "suy", "sw", "sy", "t", "u",
 ];
 
 /// Checks the implementation status of `pacman` commands in a specific file
@@ -61,7 +62,7 @@ fn make_table() -> anyhow::Result<String> {
         .map(|entry| entry.context("error while reading path"))
         .try_collect()?;
 
-    let excluded_names = ["mod.rs", "unknown.rs"];
+    let excluded_names = ["config_pm.rs", "mod.rs", "unknown.rs"];
     let impls: BTreeMap<OsString, BTreeMap<String, bool>> = paths
         .iter()
         .filter(|entry| !excluded_names.iter().any(|&ex| ex == entry.file_name()))
@@ -16,15 +16,20 @@
 //!    `.suy()`, according to the combination of flags and options obtained
 //!    above.
 
-use clap::{self, ArgAction, Parser};
+use clap::{self, ArgAction, ArgGroup, Parser, ValueEnum};
+use clap_complete::Shell;
 use figment::Figment;
+use futures::future;
 use itertools::Itertools;
 use pacaptr::{
     config::Config,
     error::{Error, Result},
-    methods,
-    pm::BoxPm,
-    print::{println, prompt},
+    exec, history,
+    manifest::Manifest,
+    methods, names,
+    package::Package,
+    pm::{self, BoxPm, PmHelper},
+    print::{println, println_err, prompt},
 };
 use tap::prelude::*;
 use tokio::task;
@@ -32,6 +37,11 @@ use tt_call::tt_call;
 
 use crate::_built::GIT_VERSION;
 
+mod completions;
+
+/// The time in seconds waited for by a bare `--wait-lock`.
+const DEFAULT_WAIT_LOCK: u64 = 300;
+
 fn version() -> &'static str {
     GIT_VERSION.unwrap_or(clap::crate_version!())
 }
@@ -51,7 +61,8 @@ pub struct Pacaptr {
     #[command(subcommand)]
     ops: Operations,
 
-    /// Specify the package manager to be invoked.
+    /// Specify the package manager to be invoked, or `all` to query every
+    /// package manager available.
     #[arg(
         global = true,
         number_of_values = 1,
@@ -62,6 +73,10 @@ pub struct Pacaptr {
     )]
     using: Option<String>,
 
+    /// Query every package manager available, same as `--using all`.
+    #[arg(global = true, long, conflicts_with = "using")]
+    all_pms: bool,
+
     /// Perform a dry run.
     #[arg(global = true, long, visible_alias = "dryrun")]
     dry_run: bool,
@@ -83,10 +98,43 @@ pub struct Pacaptr {
     #[arg(global = true, long, visible_alias = "nocache")]
     no_cache: bool,
 
+    /// Do not translate package names across package managers.
+    #[arg(global = true, long)]
+    no_translate: bool,
+
     /// Suppress log output.
     #[arg(global = true, long, conflicts_with = "dry_run")]
     quiet: bool,
 
+    /// Wait up to SECS seconds (default: 300) for the lock of the package
+    /// manager to be released before modifying the system.
+    #[arg(global = true, long, num_args = 0..=1, value_name = "SECS")]
+    #[allow(clippy::option_option)]
+    wait_lock: Option<Option<u64>>,
+
+    /// Specify the output format of query operations.
+    #[arg(
+        global = true,
+        long,
+        value_enum,
+        default_value_t,
+        value_name = "format"
+    )]
+    format: Format,
+
+    /// Hold back the given package(s) when upgrading the system.
+    #[arg(global = true, long, value_delimiter = ',', value_name = "pkg")]
+    ignore: Vec<String>,
+
+    /// Hold back the packages in the given group(s) when upgrading the system.
+    #[arg(
+        global = true,
+        long = "ignoregroup",
+        value_delimiter = ',',
+        value_name = "grp"
+    )]
+    ignore_group: Vec<String>,
+
     /// Package name or (sometimes) regex.
     #[arg(global = true, name = "KEYWORDS")]
     keywords: Vec<String>,
@@ -100,6 +148,38 @@ pub struct Pacaptr {
 #[derive(Debug, Parser)]
 #[command(about = clap::crate_description!())]
 enum Operations {
+    /// Operate on the package database.
+    #[command(
+        short_flag = 'D',
+        long_flag = "database",
+        group(ArgGroup::new("mark").args(["d", "e"]).required(true)),
+    )]
+    Database {
+        /// Mark package(s) as non-explicitly installed.
+        #[arg(long = "asdeps")]
+        d: bool,
+
+        /// Mark package(s) as explicitly installed.
+        #[arg(long = "asexplicit")]
+        e: bool,
+    },
+
+    /// Query the files database.
+    #[command(short_flag = 'F', long_flag = "files")]
+    Files {
+        /// List the files owned by the queried package.
+        #[arg(short, long = "list")]
+        l: bool,
+
+        /// Interpret each query as a regular expression.
+        #[arg(short = 'x', long = "regex")]
+        x: bool,
+
+        /// Download a fresh copy of the master files database from the server.
+        #[arg(short, long = "refresh")]
+        y: bool,
+    },
+
     /// Query the package database.
     #[command(short_flag = 'Q', long_flag = "query")]
     Query {
@@ -107,10 +187,18 @@ enum Operations {
         #[arg(short, long = "changelog")]
         c: bool,
 
+        /// Restrict or filter output to packages installed as dependencies.
+        #[arg(short, long = "deps")]
+        d: bool,
+
         /// Restrict or filter output to explicitly installed packages.
         #[arg(short, long = "explicit")]
         e: bool,
 
+        /// List the packages held back from upgrades.
+        #[arg(long = "held")]
+        h: bool,
+
         /// Display information on a given package.
         #[arg(short, long = "info", action(ArgAction::Count))]
         i: u8,
@@ -138,11 +226,21 @@ enum Operations {
         #[arg(short, long = "file")]
         p: bool,
 
+        /// Show less information for query and search, i.e. only the package
+        /// names.
+        #[arg(short)]
+        q: bool,
+
         /// Search each locally-installed package for names or descriptions that
         /// match regexp.
         #[arg(short, long = "search")]
         s: bool,
 
+        /// Restrict or filter output to packages not required by any
+        /// currently installed package.
+        #[arg(short, long = "unrequired")]
+        t: bool,
+
         /// Restrict or filter output to packages that are out-of-date on the
         /// local system.
         #[arg(short, long = "upgrades")]
@@ -167,8 +265,17 @@ enum Operations {
     },
 
     /// Synchronize packages.
-    #[command(short_flag = 'S', long_flag = "sync")]
+    #[command(
+        short_flag = 'S',
+        long_flag = "sync",
+        group(ArgGroup::new("listing").args(["g", "l", "s"]).multiple(true)),
+    )]
     Sync {
+        /// Restrict or filter output to packages from the AUR (for AUR helpers
+        /// only).
+        #[arg(short, long = "aur")]
+        a: bool,
+
         /// Remove packages that are no longer installed from the cache as well
         /// as currently unused sync databases to free up disk space.
         #[arg(short, long = "clean", action(ArgAction::Count))]
@@ -190,6 +297,11 @@ enum Operations {
         #[arg(short, long = "print")]
         p: bool,
 
+        /// Show less information for search and listing, i.e. only the
+        /// package names.
+        #[arg(short, requires = "listing")]
+        q: bool,
+
         /// Search each package in the sync databases for names or descriptions
         /// that match regexp.
         #[arg(short, long = "search")]
@@ -211,6 +323,60 @@ enum Operations {
         y: bool,
     },
 
+    /// Print the explicitly installed packages as a TOML manifest for
+    /// `sync-manifest`.
+    #[command(long_flag = "export")]
+    Export,
+
+    /// Report the pending upgrades from every package manager available,
+    /// failing if there are any.
+    #[command(long_flag = "outdated-report")]
+    OutdatedReport,
+
+    /// View the history of the operations performed, optionally filtered
+    /// with `--pm`.
+    #[command(long_flag = "history")]
+    History {
+        /// Only show the operations performed since the given time, e.g.
+        /// `2024-05-01`, `2024-05-01T12:00:00Z` or `3d`.
+        #[arg(long, value_name = "time")]
+        since: Option<String>,
+    },
+
+    /// Install the packages missing from the given TOML manifest.
+    #[command(name = "sync-manifest")]
+    SyncManifest {
+        /// Also remove the explicitly installed packages not in the manifest.
+        #[arg(long)]
+        prune: bool,
+    },
+
+    /// Check dependencies, printing the ones which are not satisfied.
+    #[command(short_flag = 'T', long_flag = "deptest")]
+    Test,
+
+    /// Print the completion script for the given shell.
+    #[command(long_flag = "completions")]
+    Completions {
+        /// The shell to be completed.
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    /// Print the names of the packages starting with the given prefix, as used
+    /// by the completion scripts.
+    #[command(long_flag = "complete-packages", hide = true)]
+    CompletePackages {
+        /// The packages to be completed.
+        #[arg(value_enum)]
+        set: PackageSet,
+    },
+
+    /// Revert the last `N` (1 by default) installations and removals recorded
+    /// in the history, optionally filtered with `--pm`.
+    #[command(long_flag = "undo")]
+    Undo,
+
     /// Upgrade or add package(s) to the system and install the required
     /// dependencies from sync repositories.
     #[command(short_flag = 'U', long_flag = "update")]
@@ -221,6 +387,101 @@ enum Operations {
     },
 }
 
+/// The output format of query operations.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// The native output of the underlying package manager.
+    #[default]
+    Text,
+
+    /// An array of package records in JSON, available for `-Q`, `-Qi`, `-Qu`,
+    /// `-Si` and `-Ss`.
+    Json,
+}
+
+/// The packages completed by `--complete-packages`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum PackageSet {
+    /// The installed packages, e.g. for `-R`.
+    Installed,
+
+    /// The packages available for installation, e.g. for `-S`.
+    Available,
+}
+
+/// Makes a [`BoxPm`] for every package manager detected with
+/// [`pm::detect_pm_strs`], as used by `--using all` and `--outdated-report`.
+fn detected_pms(cfg: &Config) -> Vec<BoxPm<'static>> {
+    pm::detect_pm_strs()
+        .into_iter()
+        .map(|name| {
+            Config {
+                default_pm: Some(name.into()),
+                ..cfg.clone()
+            }
+            .conv::<BoxPm>()
+        })
+        .collect()
+}
+
+/// Prints the given rows as a table, with the columns left-aligned.
+fn print_table<const N: usize>(rows: &[[String; N]]) {
+    let widths: [usize; N] =
+        std::array::from_fn(|i| rows.iter().map(|row| row[i].len()).max().unwrap_or(0));
+    for row in rows {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+}
+
+/// Runs the read-only operation `op` (e.g. `qs`) with `pm`, as used by
+/// `--using all`, and returns its results along with the function formatting
+/// them in the style of `pacman`.
+async fn query_packages(
+    pm: &BoxPm<'_>,
+    op: &str,
+    kws: &[&str],
+    flags: &[&str],
+) -> Result<(Vec<Package>, fn(&Package) -> String)> {
+    Ok(match op {
+        "q" => (pm.list_installed(kws, flags).await?, Package::to_q_line),
+        "qi" => (pm.info_installed(kws, flags).await?, Package::to_info_block),
+        "qs" => {
+            let patterns = exec::grep_patterns(kws)?;
+            let pkgs = pm.list_installed(&[], flags).await?;
+            let pkgs = pkgs.into_iter().filter(|pkg| pkg.matches(&patterns));
+            (pkgs.collect(), Package::to_search_entry)
+        }
+        "qu" => (
+            pm.list_upgrades(kws, flags).await?,
+            Package::to_upgrade_line,
+        ),
+        "ss" => (pm.search(kws, flags).await?, Package::to_search_entry),
+        _ => {
+            return Err(Error::ArgParseError {
+                msg: format!("`-{op}` is unsupported with `--using all`"),
+            });
+        }
+    })
+}
+
+/// Turns the failure of a package manager to list its explicitly installed
+/// packages, aka `Qe`, into an error stating that `feature` is unsupported.
+fn explicit_unsupported(e: Error, feature: &str) -> Error {
+    match e {
+        Error::OperationUnimplementedError { op, pm } if op == "list_explicit" => {
+            Error::OtherError(format!(
+                "`{feature}` is unsupported with `{pm}`, which cannot list the explicitly installed packages"
+            ))
+        }
+        e => e,
+    }
+}
+
 impl Pacaptr {
     /// Generates the current [`Config`] according to current command line
     /// arguments.
@@ -230,8 +491,13 @@ impl Pacaptr {
             needed: self.needed,
             no_confirm: self.no_confirm,
             no_cache: self.no_cache,
+            no_translate: self.no_translate,
             quiet: self.quiet.then_some(true),
             default_pm: self.using.clone(),
+            wait_lock: self.wait_lock.map(|secs| secs.unwrap_or(DEFAULT_WAIT_LOCK)),
+            ignore: self.ignore.clone(),
+            ignore_group: self.ignore_group.clone(),
+            ..Config::default()
         }
     }
 
@@ -240,7 +506,7 @@ impl Pacaptr {
     ///
     /// # Errors
     /// See [`Error`](crate::error::Error) for a list of possible errors.
-    #[allow(trivial_numeric_casts)]
+    #[allow(trivial_numeric_casts, clippy::too_many_lines)]
     async fn dispatch_from(&self, mut cfg: Config) -> Result<()> {
         /// Collect options as a `String`, eg. `-S -y -u => "Suy"`.
         ///
@@ -260,6 +526,7 @@ impl Pacaptr {
                 $( Operations::$op {
                     $( $( $key, )* )?
                     $( $( $flag, )* )?
+                    ..
                 } => {
                     options.push_str(&stringify!($op)[0..1]);
                     $( $(if $key {
@@ -273,36 +540,91 @@ impl Pacaptr {
             options.chars().sorted_unstable().pipe(String::from_iter)
         }};}
 
-        // Ensure that the cursor is not hidden when `Ctrl-C` is used.
+        // Interrupt the running commands when `Ctrl-C` is used (see
+        // `exec::interrupt`), and ensure that the cursor is not hidden afterwards.
         // See: https://github.com/console-rs/dialoguer/issues/77#issuecomment-669986406
         _ = ctrlc::set_handler(move || {
+            exec::interrupt();
             let term = console::Term::stdout();
             _ = term.show_cursor();
         })
         .tap_err(|e| println(&*prompt::INFO, e));
 
         let options = collect_options! {
+            Database {
+                flags: [d, e],
+            },
+            Files {
+                flags: [l, x, y],
+            },
+            Completions {},
+            CompletePackages {},
+            Export {},
+            History {},
+            OutdatedReport {},
             Query {
-                flags: [c, e, i, k, l, m, o, p, s, u],
+                mappings: [q -> names_only],
+                flags: [c, d, e, h, i, k, l, m, o, p, s, t, u],
             },
             Remove {
                 mappings: [p -> dry_run],
                 flags: [n, s],
             },
             Sync {
-                mappings: [p -> dry_run],
-                flags: [c, g, i, l, s, u, w, y],
+                mappings: [p -> dry_run, q -> names_only],
+                flags: [a, c, g, i, l, s, u, w, y],
             },
+            SyncManifest {},
+            Test {},
+            Undo {},
             Update {
                 mappings: [p -> dry_run],
             },
         };
 
+        match &self.ops {
+            Operations::Completions { shell } => return completions::print(*shell),
+            Operations::CompletePackages { set } => return self.complete_packages(cfg, *set).await,
+            Operations::Export => return self.export(cfg).await,
+            // `--history` only reads the log, without involving any package manager.
+            Operations::History { since } => return self.print_history(since.as_deref()),
+            Operations::OutdatedReport => return self.outdated_report(cfg).await,
+            Operations::SyncManifest { prune } => return self.sync_manifest(cfg, *prune).await,
+            Operations::Undo => return self.undo(cfg).await,
+            _ => (),
+        }
+        cfg.op = Some(options.clone());
+
+        if self.all_pms || cfg.default_pm.as_deref() == Some("all") {
+            return self.dispatch_all(cfg, &options).await;
+        }
+
+        let names = (!cfg.no_translate && names::translates(&options))
+            .then(|| names::Table::new(&cfg.names));
         let pm = cfg.conv::<BoxPm>();
 
-        let kws = self.keywords.iter().map(AsRef::as_ref).collect_vec();
+        let kws = self.keywords.iter().map(AsRef::as_ref);
+        let kws = match &names {
+            Some(names) => kws.map(|kw| names.translate(pm.name(), kw)).collect_vec(),
+            None => kws.collect_vec(),
+        };
         let flags = self.extra_flags.iter().map(AsRef::as_ref).collect_vec();
 
+        if self.format == Format::Json {
+            let pkgs = match options.to_lowercase().as_ref() {
+                "q" => pm.list_installed(&kws, &flags).await,
+                "qi" => pm.info_installed(&kws, &flags).await,
+                "qu" => pm.list_upgrades(&kws, &flags).await,
+                "si" => pm.info(&kws, &flags).await,
+                "ss" => pm.search(&kws, &flags).await,
+                _ => Err(Error::ArgParseError {
+                    msg: format!("`--format json` is unsupported for `-{options}`"),
+                }),
+            }?;
+            println!("{}", serde_json::to_string_pretty(&pkgs)?);
+            return Ok(());
+        }
+
         /// Call the method indicated by `options` on `pm`. That is:
         ///
         /// ```rust
@@ -333,6 +655,389 @@ impl Pacaptr {
         }
     }
 
+    /// Runs the read-only operation `options` with every package manager
+    /// detected, as requested by `--using all`, and prints the results grouped
+    /// by package manager, or in JSON with `--format json`.
+    ///
+    /// The package managers are queried concurrently, and those failing to
+    /// answer are reported without stopping the others, while those not
+    /// implementing `options` are reported as skipped.
+    ///
+    /// # Errors
+    /// Returns an [`Error::ArgParseError`] if `options` might modify the
+    /// system, or an [`Error::OtherError`] if no package manager has answered.
+    async fn dispatch_all(&self, cfg: Config, options: &str) -> Result<()> {
+        let op = options.to_lowercase();
+        if !matches!(op.as_str(), "q" | "qi" | "qs" | "qu" | "ss") {
+            return Err(Error::ArgParseError {
+                msg: format!(
+                    "`-{options}` is unsupported with `--using all`, \
+                     which only accepts `-Q`, `-Qi`, `-Qs`, `-Qu` and `-Ss`"
+                ),
+            });
+        }
+        let kws = self.keywords.iter().map(AsRef::as_ref).collect_vec();
+        let flags = self.extra_flags.iter().map(AsRef::as_ref).collect_vec();
+        let pms = detected_pms(&cfg);
+        let results = pms
+            .iter()
+            .map(|pm| query_packages(pm, &op, &kws, &flags))
+            .pipe(future::join_all)
+            .await;
+        let answered = results.iter().any(Result::is_ok);
+
+        if self.format == Format::Json {
+            let report = pms
+                .iter()
+                .zip(&results)
+                .map(|(pm, res)| match res {
+                    Ok((pkgs, _)) => serde_json::json!({ "pm": pm.name(), "packages": pkgs }),
+                    Err(Error::OperationUnimplementedError { .. }) => {
+                        serde_json::json!({ "pm": pm.name(), "skipped": "unsupported" })
+                    }
+                    Err(e) => serde_json::json!({ "pm": pm.name(), "error": e.to_string() }),
+                })
+                .collect_vec();
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            for (pm, res) in pms.iter().zip(results) {
+                println!("{}", console::style(format!(":: {}", pm.name())).bold());
+                match res {
+                    Ok((pkgs, fmt)) => {
+                        for pkg in &pkgs {
+                            if cfg.names_only {
+                                println!("{}", pkg.name);
+                            } else {
+                                println!("{}", fmt(pkg));
+                            }
+                        }
+                    }
+                    Err(Error::OperationUnimplementedError { .. }) => {
+                        println(&*prompt::INFO, "skipped: unsupported");
+                    }
+                    Err(e) => println_err(e),
+                }
+            }
+        }
+        if !answered {
+            return Err(Error::OtherError(format!(
+                "`-{options}` failed with every package manager"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Prints the pending upgrades reported by every package manager detected
+    /// in a table, or in JSON with `--format json`.
+    ///
+    /// The package managers are queried concurrently, and those failing to
+    /// answer or unable to list the upgrades are reported without stopping the
+    /// others.
+    ///
+    /// # Errors
+    /// Returns an [`Error::IncompleteReportError`] if any package manager has
+    /// failed or could not be checked, or else an
+    /// [`Error::PendingUpgradesError`] if any package is out of date.
+    async fn outdated_report(&self, cfg: Config) -> Result<()> {
+        let flags = self.extra_flags.iter().map(AsRef::as_ref).collect_vec();
+        // The upgrades are listed for real even in a dry run.
+        let pms = detected_pms(&Config {
+            dry_run: false,
+            ..cfg
+        });
+        let results = pms
+            .iter()
+            .map(|pm| pm.list_upgrades(&[], &flags))
+            .pipe(future::join_all)
+            .await;
+        let pending = results.iter().flatten().map(Vec::len).sum();
+        let failed = results.iter().filter(|res| res.is_err()).count();
+
+        if self.format == Format::Json {
+            let report = pms
+                .iter()
+                .zip(&results)
+                .map(|(pm, res)| match res {
+                    Ok(pkgs) => serde_json::json!({ "pm": pm.name(), "upgrades": pkgs }),
+                    Err(Error::OperationUnimplementedError { .. }) => {
+                        serde_json::json!({ "pm": pm.name(), "unchecked": "unsupported" })
+                    }
+                    Err(e) => serde_json::json!({ "pm": pm.name(), "error": e.to_string() }),
+                })
+                .collect_vec();
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            let header = ["PM", "NAME", "CURRENT", "NEW"].map(String::from);
+            let mut rows = vec![header];
+            for (pm, res) in pms.iter().zip(results) {
+                match res {
+                    Ok(pkgs) => rows.extend(pkgs.into_iter().map(|pkg| {
+                        let version = |v: Option<String>| v.unwrap_or_else(|| "?".into());
+                        [
+                            pm.name().into(),
+                            pkg.name,
+                            version(pkg.version),
+                            version(pkg.new_version),
+                        ]
+                    })),
+                    Err(Error::OperationUnimplementedError { .. }) => println_err(format!(
+                        "{}: unchecked, cannot list the pending upgrades",
+                        pm.name()
+                    )),
+                    Err(e) => println_err(format!("{}: {e}", pm.name())),
+                }
+            }
+            if pending == 0 {
+                println(&*prompt::INFO, "no pending upgrades");
+            } else {
+                print_table(&rows);
+            }
+        }
+
+        if failed > 0 {
+            Err(Error::IncompleteReportError(failed))
+        } else if pending > 0 {
+            Err(Error::PendingUpgradesError(pending))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Prints the names of the packages in `set` starting with the first
+    /// keyword, one per line, as requested by `--complete-packages`.
+    async fn complete_packages(&self, cfg: Config, set: PackageSet) -> Result<()> {
+        let prefix = self.keywords.first().map_or("", String::as_str);
+        let pm = cfg.conv::<BoxPm>();
+        let pkgs = match set {
+            PackageSet::Installed => pm.list_installed(&[], &[]).await?,
+            // Listing every package available might take ages.
+            PackageSet::Available if prefix.is_empty() => return Ok(()),
+            PackageSet::Available => pm.search(&[prefix], &[]).await?,
+        };
+        pkgs.into_iter()
+            .map(|pkg| pkg.name)
+            .filter(|name| name.starts_with(prefix))
+            .unique()
+            .for_each(|name| println!("{name}"));
+        Ok(())
+    }
+
+    /// Returns the entries of the history log, filtered by `--since` and
+    /// `--using`.
+    fn history_entries(&self, since: Option<&str>) -> Result<Vec<history::Entry>> {
+        let since = since.map(history::parse_since).transpose()?;
+        Ok(history::read()?
+            .into_iter()
+            .filter(|entry| since.is_none_or(|since| entry.time >= since))
+            .filter(|entry| self.using.as_ref().is_none_or(|pm| &entry.pm == pm))
+            .collect())
+    }
+
+    /// Prints the entries of the history log, filtered by `--since` and
+    /// `--using`.
+    fn print_history(&self, since: Option<&str>) -> Result<()> {
+        self.history_entries(since)?
+            .iter()
+            .for_each(|entry| println!("{entry}"));
+        Ok(())
+    }
+
+    /// Picks the last `n` entries that can be reverted, filtered by
+    /// `--using`, newest first and along with their inverse operations.
+    fn undo_plan<'e>(
+        &self,
+        entries: &'e [history::Entry],
+        n: usize,
+    ) -> Vec<(&'e history::Entry, &'static str)> {
+        entries
+            .iter()
+            .rev()
+            .filter(|entry| self.using.as_ref().is_none_or(|pm| &entry.pm == pm))
+            .filter_map(|entry| Some((entry, entry.inverse_op()?)))
+            .take(n)
+            .collect()
+    }
+
+    /// Prints the packages explicitly installed with the current package
+    /// manager as a [`Manifest`].
+    ///
+    /// # Errors
+    /// See [`Error`](crate::error::Error) for a list of possible errors.
+    async fn export(&self, cfg: Config) -> Result<()> {
+        let flags = self.extra_flags.iter().map(AsRef::as_ref).collect_vec();
+        // The packages are listed for real even in a dry run.
+        let pm = Config {
+            dry_run: false,
+            ..cfg
+        }
+        .conv::<BoxPm>();
+        let pkgs = pm
+            .list_explicit(&flags)
+            .await
+            .map_err(|e| explicit_unsupported(e, "--export"))?;
+        print!("{}", Manifest::from_packages(pm.name(), pkgs));
+        Ok(())
+    }
+
+    /// Installs the packages missing from the manifest given as the only
+    /// keyword, and removes the explicitly installed packages it doesn't list
+    /// if `prune` is set.
+    ///
+    /// # Errors
+    /// See [`Error`](crate::error::Error) for a list of possible errors.
+    async fn sync_manifest(&self, cfg: Config, prune: bool) -> Result<()> {
+        let [path] = &self.keywords[..] else {
+            return Err(Error::ArgParseError {
+                msg: "`sync-manifest` expects exactly one manifest".into(),
+            });
+        };
+        let flags = self.extra_flags.iter().map(AsRef::as_ref).collect_vec();
+        let manifest = Manifest::load(path)?;
+
+        // The plan is computed for real even in a dry run.
+        let query_pm = Config {
+            dry_run: false,
+            ..cfg.clone()
+        }
+        .conv::<BoxPm>();
+        let plan = manifest
+            .plan(&query_pm, &flags)
+            .await
+            .map_err(|e| explicit_unsupported(e, "sync-manifest"))?;
+        let pm = |op: &str| {
+            Config {
+                op: Some(op.into()),
+                // The packages already installed are upgraded rather than
+                // reinstalled.
+                needed: true,
+                ..cfg.clone()
+            }
+            .conv::<BoxPm>()
+        };
+
+        if !plan.install.is_empty() {
+            let kws = plan.install.iter().map(AsRef::as_ref).collect_vec();
+            pm("S").s(&kws, &flags).await?;
+            // Some of the packages might have been installed as dependencies
+            // before.
+            match pm("De").de(&kws, &flags).await {
+                Err(Error::OperationUnimplementedError { .. }) => (),
+                res => res?,
+            }
+        }
+        if !plan.install_deps.is_empty() {
+            let kws = plan.install_deps.iter().map(AsRef::as_ref).collect_vec();
+            pm("S").s(&kws, &flags).await?;
+            match pm("Dd").dd(&kws, &flags).await {
+                Err(Error::OperationUnimplementedError { .. }) => (),
+                res => res?,
+            }
+        }
+        // The latest versions available might still be too old.
+        if !(cfg.dry_run || plan.versions.is_empty()) {
+            let deps = plan.versions.iter().map(AsRef::as_ref).collect_vec();
+            query_pm.deptest(&deps, &flags).await?;
+        }
+        if !plan.extra.is_empty() {
+            if prune {
+                let kws = plan.extra.iter().map(AsRef::as_ref).collect_vec();
+                pm("R").r(&kws, &flags).await?;
+            } else {
+                let extra = plan.extra.join(" ");
+                println(&*prompt::INFO, format!("not in the manifest: {extra}"));
+            }
+        }
+        if plan.is_empty() {
+            println(&*prompt::INFO, "nothing to do");
+        }
+        Ok(())
+    }
+
+    /// Reverts the last `N` installations and removals recorded in the history
+    /// once the plan is confirmed, with the package manager's own history if
+    /// possible, or otherwise by running the inverse operations.
+    ///
+    /// # Errors
+    /// See [`Error`](crate::error::Error) for a list of possible errors.
+    async fn undo(&self, mut cfg: Config) -> Result<()> {
+        let n = match &self.keywords[..] {
+            [] => 1,
+            [n] => n
+                .parse()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| Error::ArgParseError {
+                    msg: format!("expected a positive count for `--undo`, found `{n}`"),
+                })?,
+            _ => {
+                return Err(Error::ArgParseError {
+                    msg: "`--undo` expects at most one count".into(),
+                });
+            }
+        };
+        let flags = self.extra_flags.iter().map(AsRef::as_ref).collect_vec();
+
+        let entries = history::read()?;
+        let plan = self.undo_plan(&entries, n);
+        if plan.is_empty() {
+            println(&*prompt::INFO, "nothing to undo");
+            return Ok(());
+        }
+        if let Some((entry, _)) = plan.iter().find(|(entry, _)| entry.irreversible) {
+            return Err(Error::OtherError(format!(
+                "cannot undo `-{} {}` with `{}`, which cannot tell the packages installed before apart",
+                entry.op,
+                entry.kws.join(" "),
+                entry.pm,
+            )));
+        }
+
+        for (entry, op) in &plan {
+            let kws = entry.undo_kws().join(" ");
+            println(&*prompt::PENDING, format!("[{}] -{op} {kws}", entry.pm));
+        }
+        let confirmed = cfg.dry_run
+            || cfg.no_confirm
+            || task::block_in_place(|| exec::confirm("with the undo plan?"))?;
+        if !confirmed {
+            return Ok(());
+        }
+
+        // Prefer the native history when all the operations have been performed
+        // with the same package manager, which then asks for the confirmation
+        // of its own transactions.
+        if let Ok(pm) = plan.iter().map(|(entry, _)| &entry.pm).all_equal_value() {
+            let cfg = Config {
+                default_pm: Some(pm.clone()),
+                op: Some("undo".into()),
+                ..cfg.clone()
+            };
+            match cfg.conv::<BoxPm>().undo(plan.len(), &flags).await {
+                Err(Error::OperationUnimplementedError { .. }) => (),
+                res => return res,
+            }
+        }
+
+        // The plan has been confirmed as a whole.
+        cfg.no_confirm = true;
+        for (entry, op) in plan {
+            let cfg = Config {
+                default_pm: Some(entry.pm.clone()),
+                op: Some(op.into()),
+                // Removed packages are installed back rather than reinstalled.
+                needed: true,
+                ..cfg.clone()
+            };
+            let pm = cfg.conv::<BoxPm>();
+            let kws = entry.undo_kws();
+            match op {
+                "R" => pm.r(&kws, &flags).await?,
+                _ => pm.s(&kws, &flags).await?,
+            }
+        }
+        Ok(())
+    }
+
     /// Runs [`dispatch_from`](Pacaptr::dispatch_from) with automatically
     /// detected [`Config`].
     ///
@@ -361,6 +1066,8 @@ mod tests {
 
     use std::sync::LazyLock;
 
+    use figment::providers::{Format as _, Toml};
+    use pacaptr::exec::Elevate;
     use tokio::test;
 
     use super::*;
@@ -370,6 +1077,36 @@ mod tests {
         ..Config::default()
     });
 
+    /// Runs `fut` with a history log of its own, named after the `test`, and
+    /// returns its output along with the entries recorded in the meantime.
+    async fn with_history<F: Future>(test: &str, fut: F) -> (F::Output, Vec<history::Entry>) {
+        let path =
+            std::env::temp_dir().join(format!("pacaptr-test-{}-{test}.jsonl", std::process::id()));
+        _ = std::fs::remove_file(&path);
+        let res = history::scope_path(path.clone(), async {
+            let out = fut.await;
+            (out, history::read().unwrap())
+        })
+        .await;
+        _ = std::fs::remove_file(path);
+        res
+    }
+
+    /// Makes a successful history entry of `pm` performed `days_ago`.
+    fn entry(pm: &str, op: &str, kws: &[&str], days_ago: i64) -> history::Entry {
+        history::Entry {
+            time: jiff::Timestamp::now() - jiff::SignedDuration::from_hours(24 * days_ago),
+            pm: pm.into(),
+            op: op.into(),
+            kws: kws.iter().map(|&kw| kw.into()).collect(),
+            installed: vec![],
+            irreversible: false,
+            cmd: format!("{pm} {}", kws.join(" ")),
+            status: Some(0),
+            dry_run: false,
+        }
+    }
+
     #[test]
     #[should_panic(expected = "should run: suy")]
     #[allow(clippy::semicolon_if_nothing_returned)]
@@ -401,6 +1138,362 @@ mod tests {
         opt.dispatch_from(MOCK_CFG.clone()).await.unwrap();
     }
 
+    #[test]
+    #[should_panic(expected = r#"should run: dd ["curl", "wget"]"#)]
+    #[allow(clippy::semicolon_if_nothing_returned)]
+    async fn database_asdeps() {
+        let opt = dbg!(Pacaptr::parse_from([
+            "pacaptr", "-D", "--asdeps", "curl", "wget"
+        ]));
+        let subcmd = &opt.ops;
+
+        assert!(matches!(subcmd, &Operations::Database { d, e } if d && !e));
+        assert_eq!(opt.keywords, &["curl", "wget"]);
+        assert!(Pacaptr::try_parse_from(["pacaptr", "-D", "curl"]).is_err());
+        assert!(Pacaptr::try_parse_from(["pacaptr", "-D", "--asdeps", "--asexplicit"]).is_err());
+
+        opt.dispatch_from(MOCK_CFG.clone()).await.unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = r#"should run: fx ["^/usr/bin/.*fish$"]"#)]
+    #[allow(clippy::semicolon_if_nothing_returned)]
+    async fn files_regex() {
+        let opt = dbg!(Pacaptr::parse_from(["pacaptr", "-Fx", "^/usr/bin/.*fish$"]));
+        let subcmd = &opt.ops;
+
+        assert!(matches!(subcmd, &Operations::Files { x, .. } if x));
+        assert_eq!(opt.keywords, &["^/usr/bin/.*fish$"]);
+
+        opt.dispatch_from(MOCK_CFG.clone()).await.unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = r#"should run: t ["curl>=8", "wget"]"#)]
+    #[allow(clippy::semicolon_if_nothing_returned)]
+    async fn deptest() {
+        let opt = dbg!(Pacaptr::parse_from(["pacaptr", "-T", "curl>=8", "wget"]));
+        let subcmd = &opt.ops;
+
+        assert!(matches!(subcmd, &Operations::Test));
+        assert_eq!(opt.keywords, &["curl>=8", "wget"]);
+        assert_eq!(Error::UnsatisfiedDepsError(2).exit_code(), 127);
+
+        opt.dispatch_from(MOCK_CFG.clone()).await.unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "should run: qdt")]
+    #[allow(clippy::semicolon_if_nothing_returned)]
+    async fn query_orphans() {
+        let opt = dbg!(Pacaptr::parse_from(["pacaptr", "-Qtd"]));
+        let subcmd = &opt.ops;
+
+        assert!(matches!(subcmd, &Operations::Query { d, t, .. } if d && t));
+        assert!(opt.keywords.is_empty());
+
+        opt.dispatch_from(MOCK_CFG.clone()).await.unwrap();
+    }
+
+    #[test]
+    async fn export() {
+        let opt = dbg!(Pacaptr::parse_from(["pacaptr", "--export", "--pm", "brew"]));
+        let subcmd = &opt.ops;
+
+        assert!(matches!(subcmd, Operations::Export));
+        assert_eq!(opt.using.as_deref(), Some("brew"));
+
+        // Package managers defined in the config file cannot list the
+        // explicitly installed packages.
+        let toml = indoc::indoc! {r#"
+            default_pm = "opkg"
+
+            [pms.opkg.q]
+            cmd = ["opkg", "list-installed"]
+        "#};
+        let cfg: Config = Figment::from(Toml::string(toml)).extract().unwrap();
+        let opt = dbg!(Pacaptr::parse_from(["pacaptr", "--export"]));
+        let err = opt.dispatch_from(cfg).await.unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("`--export` is unsupported with `opkg`"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    async fn outdated_report() {
+        let opt = dbg!(Pacaptr::parse_from([
+            "pacaptr",
+            "--outdated-report",
+            "--format",
+            "json"
+        ]));
+        let subcmd = &opt.ops;
+
+        assert!(matches!(subcmd, Operations::OutdatedReport));
+        assert_eq!(opt.format, Format::Json);
+        assert_eq!(Error::PendingUpgradesError(2).exit_code(), 100);
+        assert_eq!(Error::IncompleteReportError(1).exit_code(), 101);
+    }
+
+    #[test]
+    async fn history_since() {
+        let opt = dbg!(Pacaptr::parse_from([
+            "pacaptr",
+            "--history",
+            "--since",
+            "3d",
+            "--pm",
+            "apt"
+        ]));
+        let subcmd = &opt.ops;
+
+        assert!(matches!(subcmd, Operations::History { since } if since.as_deref() == Some("3d")));
+        assert_eq!(opt.using.as_deref(), Some("apt"));
+
+        let (res, _) = with_history("history_since", async {
+            history::append(&entry("apt", "S", &["fish"], 5)).unwrap();
+            history::append(&entry("apt", "R", &["wget"], 1)).unwrap();
+            history::append(&entry("brew", "S", &["curl"], 1)).unwrap();
+            opt.history_entries(Some("3d"))
+        })
+        .await;
+        let entries = res.unwrap();
+        assert!(
+            matches!(&entries[..], [entry] if entry.pm == "apt" && entry.kws == ["wget"]),
+            "{entries:?}"
+        );
+    }
+
+    #[test]
+    async fn sync_manifest() {
+        let opt = dbg!(Pacaptr::parse_from([
+            "pacaptr",
+            "sync-manifest",
+            "packages.toml",
+            "--prune"
+        ]));
+        let subcmd = &opt.ops;
+
+        assert!(matches!(subcmd, Operations::SyncManifest { prune } if *prune));
+        assert_eq!(opt.keywords, ["packages.toml"]);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"should run: s ["fish"]"#)]
+    #[allow(clippy::semicolon_if_nothing_returned)]
+    async fn sync_manifest_install() {
+        let path =
+            std::env::temp_dir().join(format!("pacaptr-test-{}-install.toml", std::process::id()));
+        std::fs::write(&path, "[packages]\ncurl = {}\nfish = {}\n").unwrap();
+        let opt = dbg!(Pacaptr::parse_from([
+            "pacaptr",
+            "sync-manifest",
+            path.to_str().unwrap(),
+        ]));
+
+        // `curl` is installed already, and `wget` is kept without `--prune`.
+        opt.dispatch_from(MOCK_CFG.clone()).await.unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = r#"should run: r ["wget"]"#)]
+    #[allow(clippy::semicolon_if_nothing_returned)]
+    async fn sync_manifest_prune() {
+        let path =
+            std::env::temp_dir().join(format!("pacaptr-test-{}-prune.toml", std::process::id()));
+        std::fs::write(&path, "[packages]\ncurl = {}\n").unwrap();
+        let opt = dbg!(Pacaptr::parse_from([
+            "pacaptr",
+            "sync-manifest",
+            path.to_str().unwrap(),
+            "--prune",
+        ]));
+
+        opt.dispatch_from(MOCK_CFG.clone()).await.unwrap();
+    }
+
+    #[test]
+    async fn undo_count() {
+        let opt = dbg!(Pacaptr::parse_from([
+            "pacaptr", "--undo", "2", "--pm", "apt"
+        ]));
+        let subcmd = &opt.ops;
+
+        assert!(matches!(subcmd, Operations::Undo));
+        assert_eq!(opt.keywords, ["2"]);
+        assert_eq!(opt.using.as_deref(), Some("apt"));
+
+        let failed = history::Entry {
+            status: Some(100),
+            ..entry("apt", "S", &["nope"], 0)
+        };
+        let entries = [
+            entry("apt", "S", &["fish"], 3),
+            entry("apt", "S", &["curl"], 2),
+            entry("apt", "Rs", &["wget"], 1),
+            entry("brew", "S", &["jq"], 1),
+            failed,
+        ];
+        // The last two operations of `apt` that have succeeded, newest first.
+        let plan = opt
+            .undo_plan(&entries, 2)
+            .into_iter()
+            .map(|(entry, op)| (op, entry.undo_kws()))
+            .collect_vec();
+        assert_eq!(plan, [("S", vec!["wget"]), ("R", vec!["curl"])]);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"should run: s ["wget"]"#)]
+    #[allow(clippy::semicolon_if_nothing_returned)]
+    async fn undo_run() {
+        let opt = dbg!(Pacaptr::parse_from(["pacaptr", "--undo"]));
+        let cfg = Config {
+            no_confirm: true,
+            ..MOCK_CFG.clone()
+        };
+        let (res, _) = with_history("undo_run", async {
+            history::append(&entry("mockpm", "S", &["fish"], 1)).unwrap();
+            history::append(&entry("mockpm", "R", &["wget"], 0)).unwrap();
+            opt.dispatch_from(cfg).await
+        })
+        .await;
+        res.unwrap();
+    }
+
+    #[test]
+    async fn undo_irreversible() {
+        // Without `installed_version`, the packages installed before are unknown.
+        let unknown = entry("apt", "S", &["fish", "curl"], 0).installed(None);
+        let opt = dbg!(Pacaptr::parse_from(["pacaptr", "--undo"]));
+        let (res, _) = with_history("undo_irreversible", async {
+            history::append(&unknown).unwrap();
+            opt.dispatch_from(MOCK_CFG.clone()).await
+        })
+        .await;
+        assert!(
+            matches!(res, Err(Error::OtherError(msg)) if msg.starts_with("cannot undo `-S fish curl`"))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"should run: ss ["curl"]"#)]
+    #[allow(clippy::semicolon_if_nothing_returned)]
+    async fn search_names_only() {
+        let opt = dbg!(Pacaptr::parse_from(["pacaptr", "-Ssq", "curl"]));
+        let subcmd = &opt.ops;
+
+        assert!(matches!(subcmd, &Operations::Sync { q, s, .. } if q && s));
+        assert_eq!(opt.keywords, &["curl"]);
+        assert!(Pacaptr::try_parse_from(["pacaptr", "-Sq", "curl"]).is_err());
+
+        opt.dispatch_from(MOCK_CFG.clone()).await.unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "should run: qh")]
+    #[allow(clippy::semicolon_if_nothing_returned)]
+    async fn query_held() {
+        let opt = dbg!(Pacaptr::parse_from(["pacaptr", "-Q", "--held"]));
+        let subcmd = &opt.ops;
+
+        assert!(matches!(subcmd, &Operations::Query { h, .. } if h));
+        assert!(opt.keywords.is_empty());
+
+        opt.dispatch_from(MOCK_CFG.clone()).await.unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "should run: sau")]
+    #[allow(clippy::semicolon_if_nothing_returned)]
+    async fn simple_sua() {
+        let opt = dbg!(Pacaptr::parse_from(["pacaptr", "-Sua"]));
+        let subcmd = &opt.ops;
+
+        assert!(matches!(subcmd, &Operations::Sync { a, u, .. } if a && u));
+        assert!(opt.keywords.is_empty());
+
+        opt.dispatch_from(MOCK_CFG.clone()).await.unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = r#"should run: s ["fd-find", "curl"]"#)]
+    #[allow(clippy::semicolon_if_nothing_returned)]
+    async fn sync_translate() {
+        let opt = dbg!(Pacaptr::parse_from(["pacaptr", "-S", "fd", "curl"]));
+        let subcmd = &opt.ops;
+
+        assert!(matches!(subcmd, &Operations::Sync { .. }));
+        assert_eq!(opt.keywords, &["fd", "curl"]);
+
+        let mut cfg = MOCK_CFG.clone();
+        cfg.names
+            .entry("fd".into())
+            .or_default()
+            .insert("mockpm".into(), "fd-find".into());
+        opt.dispatch_from(cfg).await.unwrap();
+    }
+
+    #[test]
+    async fn names_translate() {
+        let names = names::Table::new(&names::Names::default());
+        assert_eq!(names.translate("apt", "fd"), "fd-find");
+        assert_eq!(names.translate("pacman", "fd-find"), "fd");
+        assert_eq!(names.translate("xbps", "sqlite3"), "sqlite");
+        assert_eq!(names.translate("apt", "curl"), "curl");
+        // `pip` and `conda` have namespaces of their own.
+        assert_eq!(names.translate("pip", "sqlite3"), "sqlite3");
+        assert_eq!(names.translate("conda", "python3"), "python3");
+    }
+
+    #[test]
+    #[should_panic(expected = r#"should run: s ["fd"]"#)]
+    #[allow(clippy::semicolon_if_nothing_returned)]
+    async fn sync_no_translate() {
+        let opt = dbg!(Pacaptr::parse_from([
+            "pacaptr",
+            "-S",
+            "fd",
+            "--no-translate"
+        ]));
+        let subcmd = &opt.ops;
+
+        assert!(matches!(subcmd, &Operations::Sync { .. }));
+        assert!(opt.no_translate);
+
+        let mut cfg = opt.cfg().join(MOCK_CFG.clone());
+        cfg.names
+            .entry("fd".into())
+            .or_default()
+            .insert("mockpm".into(), "fd-find".into());
+        opt.dispatch_from(cfg).await.unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "should run: suy")]
+    #[allow(clippy::semicolon_if_nothing_returned)]
+    async fn sync_ignore() {
+        let opt = dbg!(Pacaptr::parse_from([
+            "pacaptr",
+            "-Syu",
+            "--ignore",
+            "linux,nvidia",
+            "--ignore=mysql",
+            "--ignoregroup",
+            "gnome",
+        ]));
+        let subcmd = &opt.ops;
+
+        assert!(matches!(subcmd, &Operations::Sync { u, y, .. } if y && u));
+        assert_eq!(opt.ignore, &["linux", "nvidia", "mysql"]);
+        assert_eq!(opt.ignore_group, &["gnome"]);
+        assert!(opt.keywords.is_empty());
+
+        opt.dispatch_from(MOCK_CFG.clone()).await.unwrap();
+    }
+
     #[test]
     #[should_panic(expected = r#"should run: sw ["curl", "wget"]"#)]
     #[allow(clippy::semicolon_if_nothing_returned)]
@@ -453,6 +1546,22 @@ mod tests {
         opt.dispatch_from(MOCK_CFG.clone()).await.unwrap();
     }
 
+    #[test]
+    #[should_panic(expected = r#"should run: list_upgrades ["curl"]"#)]
+    #[allow(clippy::semicolon_if_nothing_returned)]
+    async fn format_json() {
+        let opt = dbg!(Pacaptr::parse_from([
+            "pacaptr", "-Qu", "--format", "json", "curl"
+        ]));
+        let subcmd = &opt.ops;
+
+        assert_eq!(opt.format, Format::Json);
+        assert!(matches!(subcmd, &Operations::Query { u, .. } if u));
+        assert_eq!(opt.keywords, &["curl"]);
+
+        opt.dispatch_from(MOCK_CFG.clone()).await.unwrap();
+    }
+
     #[test]
     #[should_panic(expected = r#"should run: si ["docker", "--proxy=localhost:1234"]"#)]
     #[allow(clippy::semicolon_if_nothing_returned)]
@@ -476,4 +1585,281 @@ mod tests {
 
         opt.dispatch_from(MOCK_CFG.clone()).await.unwrap();
     }
+
+    #[test]
+    async fn config_pm() {
+        let toml = indoc::indoc! {r#"
+            default_pm = "opkg"
+            dry_run = true
+
+            [pms.opkg.s]
+            cmd = ["opkg", "install"]
+            sudo = true
+            prompt = { native_no_confirm = ["--force-yes"] }
+
+            [pms.opkg.r]
+            cmd = ["opkg", "remove"]
+        "#};
+        let cfg: Config = Figment::from(Toml::string(toml)).extract().unwrap();
+        let def = &cfg.pms["opkg"]["s"];
+        assert_eq!(def.cmd, ["opkg", "install"]);
+        assert!(def.sudo);
+
+        let opt = dbg!(Pacaptr::parse_from(["pacaptr", "-S", "curl"]));
+        let (res, entries) = with_history("config_pm", opt.dispatch_from(cfg.clone())).await;
+        res.unwrap();
+        assert!(matches!(&entries[..], [entry] if entry.op == "S" && entry.dry_run));
+
+        let opt = dbg!(Pacaptr::parse_from(["pacaptr", "-Q"]));
+        let err = opt.dispatch_from(cfg).await.unwrap_err();
+        assert!(
+            matches!(err, Error::OperationUnimplementedError { op, pm } if op == "q" && pm == "opkg")
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    async fn config_pm_kws() {
+        let toml = indoc::indoc! {r#"
+            default_pm = "opkg"
+
+            [pms.opkg.q]
+            cmd = ["sh", "-c", "test \"$*\" = '-V curl fish --end'", "sh", "{kws}", "--end"]
+        "#};
+        let cfg: Config = Figment::from(Toml::string(toml)).extract().unwrap();
+
+        let opt = dbg!(Pacaptr::parse_from([
+            "pacaptr", "-Q", "curl", "fish", "--", "-V"
+        ]));
+        opt.dispatch_from(cfg.clone()).await.unwrap();
+
+        let opt = dbg!(Pacaptr::parse_from(["pacaptr", "-Q", "curl"]));
+        let err = opt.dispatch_from(cfg).await.unwrap_err();
+        assert!(matches!(err, Error::CmdStatusCodeError { code: 1, .. }));
+    }
+
+    #[test]
+    async fn all_pms_mutating() {
+        let opt = dbg!(Pacaptr::parse_from(["pacaptr", "-S", "--all-pms", "curl"]));
+        assert!(opt.all_pms);
+
+        let err = opt.dispatch_from(MOCK_CFG.clone()).await.unwrap_err();
+        assert!(matches!(err, Error::ArgParseError { msg } if msg.contains("--using all")));
+    }
+
+    #[test]
+    async fn elevate_config() {
+        let parse = |toml| Figment::from(Toml::string(toml)).extract::<Config>().ok();
+
+        let cfg = parse(r#"elevate = "doas""#).unwrap();
+        assert_eq!(cfg.elevate(), Elevate::Doas);
+
+        let cfg = parse(r#"elevate = ["su", "-c", "{}"]"#).unwrap();
+        assert_eq!(
+            cfg.elevate(),
+            Elevate::Custom(vec!["su".into(), "-c".into(), "{}".into()])
+        );
+
+        let cfg = parse(r#"default_pm = "apt""#).unwrap();
+        assert_eq!(cfg.elevate(), Elevate::detect());
+
+        assert!(parse(r#"elevate = "bogus""#).is_none());
+        assert!(parse("elevate = []").is_none());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    async fn classify_failures() {
+        let toml = indoc::indoc! {r#"
+            default_pm = "locked"
+
+            [pms.locked.ss]
+            cmd = ["sh", "-c", "echo 'E: Could not get lock /var/lib/dpkg/lock' >&2; exit 100"]
+        "#};
+        let cfg: Config = Figment::from(Toml::string(toml)).extract().unwrap();
+        let opt = dbg!(Pacaptr::parse_from(["pacaptr", "-Ss"]));
+        let err = opt.dispatch_from(cfg).await.unwrap_err();
+        assert!(matches!(err, Error::LockHeldError { code: 100, .. }));
+        assert_eq!(err.exit_code(), 75);
+        assert!(err.hint().is_some());
+
+        let apt = Config {
+            default_pm: Some("apt".into()),
+            ..Config::default()
+        }
+        .conv::<BoxPm>();
+        let classify = |output: &str| {
+            apt.classify(Error::CmdStatusCodeError {
+                code: 100,
+                output: output.into(),
+            })
+        };
+        assert!(matches!(
+            classify("E: Unable to locate package fsih"),
+            Error::PackageNotFoundError { .. }
+        ));
+        assert!(matches!(
+            classify(indoc::indoc! {"
+                E: Could not open lock file /var/lib/dpkg/lock-frontend - open (13: Permission denied)
+                E: Unable to acquire the dpkg frontend lock (/var/lib/dpkg/lock-frontend), are you root?
+            "}),
+            Error::PermissionDeniedError { .. }
+        ));
+        let err = classify("E: Sub-process /usr/bin/dpkg returned an error code (1)");
+        assert!(matches!(err, Error::CmdStatusCodeError { .. }));
+        assert_eq!(err.exit_code(), 100);
+        assert!(err.hint().is_none());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    async fn wait_lock() {
+        use pacaptr::lock::{self, Lock};
+
+        let opt = dbg!(Pacaptr::parse_from([
+            "pacaptr",
+            "-S",
+            "fish",
+            "--wait-lock"
+        ]));
+        assert_eq!(opt.cfg().wait_lock, Some(300));
+        assert_eq!(opt.keywords, ["fish"]);
+        let opt = dbg!(Pacaptr::parse_from([
+            "pacaptr",
+            "-S",
+            "fish",
+            "--wait-lock",
+            "60"
+        ]));
+        assert_eq!(opt.cfg().wait_lock, Some(60));
+        assert_eq!(opt.keywords, ["fish"]);
+        let opt = dbg!(Pacaptr::parse_from([
+            "pacaptr",
+            "-S",
+            "--wait-lock=5",
+            "fish"
+        ]));
+        assert_eq!(opt.cfg().wait_lock, Some(5));
+        assert_eq!(opt.keywords, ["fish"]);
+        assert!(Pacaptr::try_parse_from(["pacaptr", "-S", "--wait-lock", "fish"]).is_err());
+
+        let dir = std::env::temp_dir();
+        let pid = i32::try_from(std::process::id()).unwrap();
+
+        let lock_path = dir.join(format!("pacaptr-test-{pid}.lock"));
+        let file = std::fs::File::create(&lock_path).unwrap();
+        let lock = Lock::File(lock_path.to_str().unwrap().to_owned().leak());
+        file.lock().unwrap();
+        assert_eq!(lock.holder().and_then(|holder| holder.pid), Some(pid));
+        let err = lock::wait(&[lock], std::time::Duration::ZERO, "S")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::LockTimeoutError { .. }));
+        file.unlock().unwrap();
+        assert_eq!(lock.holder(), None);
+        lock::wait(&[lock], std::time::Duration::ZERO, "S")
+            .await
+            .unwrap();
+
+        let pid_path = dir.join(format!("pacaptr-test-{pid}.pid"));
+        let lock = Lock::PidFile(pid_path.to_str().unwrap().to_owned().leak());
+        std::fs::write(&pid_path, format!("{pid}\n")).unwrap();
+        assert_eq!(lock.holder().and_then(|holder| holder.pid), Some(pid));
+        std::fs::write(&pid_path, "0").unwrap();
+        assert_eq!(lock.holder(), None);
+
+        _ = std::fs::remove_file(lock_path);
+        _ = std::fs::remove_file(pid_path);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    async fn retry_network() {
+        let dir = std::env::temp_dir();
+        let log = dir.join(format!("pacaptr-test-{}.retries", std::process::id()));
+        let toml = indoc::formatdoc! {r#"
+            default_pm = "flaky"
+            retries = 2
+            backoff = 0
+
+            [pms.flaky.sy]
+            cmd = ["sh", "-c", "echo >> {log}; echo 'Temporary failure resolving' >&2; exit 100"]
+
+            [pms.flaky.s]
+            cmd = ["sh", "-c", "echo >> {log}; echo 'No space left on device' >&2; exit 100"]
+        "#, log = log.display()};
+        let cfg: Config = Figment::from(Toml::string(&toml)).extract().unwrap();
+        let attempts = || {
+            let n = std::fs::read_to_string(&log)
+                .unwrap_or_default()
+                .lines()
+                .count();
+            _ = std::fs::remove_file(&log);
+            n
+        };
+
+        let ((), entries) = Box::pin(with_history("retry_network", async {
+            let opt = dbg!(Pacaptr::parse_from(["pacaptr", "-Sy"]));
+            let err = opt.dispatch_from(cfg.clone()).await.unwrap_err();
+            assert!(matches!(err, Error::NetworkError { .. }));
+            assert_eq!(attempts(), 3);
+
+            let opt = dbg!(Pacaptr::parse_from(["pacaptr", "-S", "fish"]));
+            let err = opt.dispatch_from(cfg.clone()).await.unwrap_err();
+            assert!(matches!(err, Error::DiskFullError { .. }));
+            assert_eq!(attempts(), 1);
+
+            // Interrupting the backoff gives up on the remaining attempts.
+            let cfg = Config {
+                backoff: Some(60),
+                ..cfg
+            };
+            let opt = dbg!(Pacaptr::parse_from(["pacaptr", "-Sy"]));
+            let (res, ()) = exec::scope_interrupts(async {
+                tokio::join!(opt.dispatch_from(cfg), async {
+                    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                    exec::interrupt();
+                })
+            })
+            .await;
+            let err = res.unwrap_err();
+            assert!(matches!(err, Error::CmdInterruptedError { op } if op == "-Sy"));
+            assert_eq!(attempts(), 1);
+        }))
+        .await;
+        // Only the last attempt of each command is recorded.
+        let ops = entries.iter().map(|entry| entry.op.as_str()).collect_vec();
+        assert_eq!(ops, ["Sy", "S", "Sy"]);
+    }
+
+    #[test]
+    async fn completions() {
+        let opt = dbg!(Pacaptr::parse_from(["pacaptr", "--completions", "zsh"]));
+        assert!(matches!(
+            opt.ops,
+            Operations::Completions { shell: Shell::Zsh }
+        ));
+        for shell in Shell::value_variants() {
+            completions::print(*shell).unwrap();
+        }
+        // The package names are completed on top of the generated flags.
+        let zsh = completions::script(Shell::Zsh).unwrap();
+        assert!(zsh.contains("\n_pacaptr_flags() {"));
+        assert!(zsh.contains("_pacaptr_flags \"$@\""));
+        assert!(zsh.contains("--complete-packages"));
+
+        let opt = dbg!(Pacaptr::parse_from([
+            "pacaptr",
+            "--complete-packages",
+            "installed",
+            "fi",
+        ]));
+        assert!(matches!(
+            opt.ops,
+            Operations::CompletePackages {
+                set: PackageSet::Installed
+            }
+        ));
+        assert_eq!(opt.keywords, ["fi"]);
+    }
 }
@@ -0,0 +1,138 @@
+//! Shell completions for [`Pacaptr`], as printed by `--completions`.
+//!
+//! On top of the flags, the scripts for `bash`, `zsh` and `fish` complete the
+//! package names after `-Q`, `-R` and `-D` (the installed ones) and `-S` (the
+//! available ones) by calling `pacaptr --complete-packages`.
+
+use clap::CommandFactory;
+use clap_complete::{Shell, generate};
+use indoc::indoc;
+use pacaptr::error::Result;
+
+use super::Pacaptr;
+
+/// The name of the binary, as known to the shells.
+const BIN_NAME: &str = clap::crate_name!();
+
+/// The `bash` function completing the package names, which falls back to the
+/// generated `_pacaptr` otherwise.
+const BASH_PACKAGES: &str = indoc! {r#"
+    _pacaptr_packages() {
+        local cur="${COMP_WORDS[COMP_CWORD]}" set= prev= word
+        local -a args=()
+        if [[ $cur != -* ]]; then
+            for word in "${COMP_WORDS[@]:1:COMP_CWORD-1}"; do
+                case $word in
+                    --) set=; break ;;
+                    -Q* | -R* | -D* | --query | --remove | --database) set=installed ;;
+                    -S* | --sync) set=available ;;
+                esac
+                case $prev in
+                    --using | --pm | --package-manager) args=(--using "$word") ;;
+                esac
+                prev=$word
+            done
+        fi
+        if [[ -n $set ]]; then
+            COMPREPLY=($(compgen -W "$(pacaptr "${args[@]}" --complete-packages "$set" "$cur" 2>/dev/null)" -- "$cur"))
+            [[ ${#COMPREPLY[@]} -gt 0 ]] && return
+        fi
+        _pacaptr "$@"
+    }
+
+    complete -F _pacaptr_packages -o nosort -o bashdefault -o default pacaptr
+"#};
+
+/// The head of the generated `zsh` completion function, to be renamed to
+/// `_pacaptr_flags`.
+const ZSH_MAIN: &str = "\n_pacaptr() {";
+
+/// The tail of the generated `zsh` script, which either calls `_pacaptr` right
+/// away (when autoloaded from `$fpath`) or registers it with `compdef` (when
+/// sourced).
+const ZSH_TAIL: &str = "\nif [ \"$funcstack[1]\" = \"_pacaptr\" ]";
+
+/// The `zsh` function completing the package names, which falls back to the
+/// generated one (renamed to `_pacaptr_flags`) otherwise.
+const ZSH_PACKAGES: &str = indoc! {r#"
+    _pacaptr() {
+        local set prev word
+        local -a args pkgs
+        if [[ $PREFIX != -* ]]; then
+            for word in ${words[2,CURRENT-1]}; do
+                case $word in
+                    (--) set=; break ;;
+                    (-Q*|-R*|-D*|--query|--remove|--database) set=installed ;;
+                    (-S*|--sync) set=available ;;
+                esac
+                [[ $prev == (--using|--pm|--package-manager) ]] && args=(--using $word)
+                prev=$word
+            done
+        fi
+        if [[ -n $set ]]; then
+            pkgs=(${(f)"$(_call_program packages pacaptr $args --complete-packages $set "$PREFIX" 2>/dev/null)"})
+            compadd -a pkgs && return
+        fi
+        _pacaptr_flags "$@"
+    }
+"#};
+
+/// The `fish` function completing the package names.
+const FISH_PACKAGES: &str = indoc! {r#"
+    function __pacaptr_packages
+        set -l tokens (commandline -opc)
+        set -e tokens[1]
+        set -l set
+        set -l prev
+        set -l args
+        for token in $tokens
+            switch $token
+                case '--'
+                    return
+                case '-Q*' '-R*' '-D*' --query --remove --database
+                    set set installed
+                case '-S*' --sync
+                    set set available
+            end
+            contains -- $prev --using --pm --package-manager; and set args --using $token
+            set prev $token
+        end
+        test -n "$set"; and pacaptr $args --complete-packages $set (commandline -ct) 2>/dev/null
+    end
+
+    complete -c pacaptr -f -a '(__pacaptr_packages)'
+"#};
+
+/// Returns the completion script for `shell`.
+///
+/// # Errors
+/// Returns an [`Error::FromUtf8Error`](pacaptr::error::Error::FromUtf8Error)
+/// if the generated script is not valid UTF-8.
+pub fn script(shell: Shell) -> Result<String> {
+    let mut script = vec![];
+    generate(shell, &mut Pacaptr::command(), BIN_NAME, &mut script);
+    let script = String::from_utf8(script)?;
+    Ok(match shell {
+        Shell::Bash => format!("{script}\n{BASH_PACKAGES}"),
+        // `_pacaptr` takes the place of the generated function, so as to be
+        // used both when autoloaded and when sourced.
+        Shell::Zsh => match script.rsplit_once(ZSH_TAIL) {
+            Some((defs, tail)) if defs.contains(ZSH_MAIN) => {
+                let defs = defs.replacen(ZSH_MAIN, "\n_pacaptr_flags() {", 1);
+                format!("{defs}\n{ZSH_PACKAGES}{ZSH_TAIL}{tail}")
+            }
+            _ => script,
+        },
+        Shell::Fish => format!("{script}\n{FISH_PACKAGES}"),
+        _ => script,
+    })
+}
+
+/// Prints the completion script for `shell`.
+///
+/// # Errors
+/// See [`script`].
+pub fn print(shell: Shell) -> Result<()> {
+    print!("{}", script(shell)?);
+    Ok(())
+}
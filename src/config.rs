@@ -9,16 +9,19 @@
 //!   environment variable. For example, `PACAPTR_NEEDED=false` is prioritized
 //!   over `needed = true` in `pacaptr.toml`.
 
-use std::{env, path::PathBuf};
+use std::{collections::BTreeMap, env, path::PathBuf, time::Duration};
 
 use figment::{
     Figment, Provider,
     providers::{Env, Format, Toml},
     util::bool_from_str_or_int,
 };
+use itertools::Itertools;
 use serde::{Deserialize, Deserializer, Serialize};
 use tap::prelude::*;
 
+use crate::{exec::Elevate, names::Names, pm::PmDef};
+
 /// The crate name.
 const CRATE_NAME: &str = clap::crate_name!();
 
@@ -49,12 +52,59 @@ pub struct Config {
     #[serde(default, deserialize_with = "bool_from_str_or_int")]
     pub no_cache: bool,
 
+    /// Do not translate package names across package managers.
+    #[serde(default, deserialize_with = "bool_from_str_or_int")]
+    pub no_translate: bool,
+
+    /// Print only the package names when listing packages.
+    #[serde(skip)]
+    pub names_only: bool,
+
     /// Suppress log output.
     #[serde(default, deserialize_with = "option_bool_from_str_or_int")]
     pub quiet: Option<bool>,
 
     /// The default package manager to be invoked.
     pub default_pm: Option<String>,
+
+    /// The way in which the privileges of a normal admin are gained, e.g.
+    /// `"doas"`.
+    pub elevate: Option<Elevate>,
+
+    /// The number of seconds to wait for the lock of the package manager to be
+    /// released before modifying the system.
+    pub wait_lock: Option<u64>,
+
+    /// The number of times a command of `-S`, `-Sy`, `-Su` or `-Sw` is retried
+    /// after a network failure.
+    pub retries: Option<u32>,
+
+    /// The number of seconds to wait before the first retry, which is doubled
+    /// for each retry after that.
+    pub backoff: Option<u64>,
+
+    /// Packages to be held back when upgrading the system.
+    #[serde(default)]
+    pub ignore: Vec<String>,
+
+    /// Package groups to be held back when upgrading the system.
+    #[serde(default)]
+    pub ignore_group: Vec<String>,
+
+    /// Extra translations of package names across package managers, e.g.
+    /// `names.fd.apt = "fd-find"`.
+    #[serde(default)]
+    pub names: Names,
+
+    /// User-defined package managers, indexed by name, each mapping `pacman`
+    /// methods to commands, e.g. `pms.opkg.s.cmd = ["opkg", "install"]`.
+    #[serde(default)]
+    pub pms: BTreeMap<String, PmDef>,
+
+    /// The `pacman` operation being performed, e.g. `Suy`, as recorded in the
+    /// history log.
+    #[serde(skip)]
+    pub op: Option<String>,
 }
 
 fn option_bool_from_str_or_int<'de, D: Deserializer<'de>>(de: D) -> Result<Option<bool>, D::Error> {
@@ -70,6 +120,20 @@ impl Config {
             .unwrap_or_else(|| !console::Term::stdout().is_term())
     }
 
+    /// Returns the value of the `elevate` item if it is present,
+    /// otherwise returns the one detected from `$PATH`.
+    #[must_use]
+    pub fn elevate(&self) -> Elevate {
+        self.elevate.clone().unwrap_or_else(Elevate::detect)
+    }
+
+    /// Returns the delay before the first retry after a network failure, as
+    /// given by the `backoff` item (2 seconds by default).
+    #[must_use]
+    pub fn backoff(&self) -> Duration {
+        Duration::from_secs(self.backoff.unwrap_or(2))
+    }
+
     /// Performs a left-biased join of two `Config`s.
     pub fn join(&self, other: Self) -> Self {
         Self {
@@ -77,8 +141,41 @@ impl Config {
             needed: self.needed || other.dry_run,
             no_confirm: self.no_confirm || other.no_confirm,
             no_cache: self.no_cache || other.no_cache,
+            no_translate: self.no_translate || other.no_translate,
+            names_only: self.names_only || other.names_only,
             quiet: self.quiet.or(other.quiet),
             default_pm: self.default_pm.clone().or(other.default_pm),
+            elevate: self.elevate.clone().or(other.elevate),
+            wait_lock: self.wait_lock.or(other.wait_lock),
+            retries: self.retries.or(other.retries),
+            backoff: self.backoff.or(other.backoff),
+            ignore: self
+                .ignore
+                .iter()
+                .chain(&other.ignore)
+                .unique()
+                .cloned()
+                .collect(),
+            ignore_group: self
+                .ignore_group
+                .iter()
+                .chain(&other.ignore_group)
+                .unique()
+                .cloned()
+                .collect(),
+            names: self
+                .names
+                .iter()
+                .fold(other.names, |mut names, (name, entry)| {
+                    let entry = entry.iter().map(|(pm, name)| (pm.clone(), name.clone()));
+                    names.entry(name.clone()).or_default().extend(entry);
+                    names
+                }),
+            pms: self.pms.iter().fold(other.pms, |mut pms, (name, def)| {
+                pms.insert(name.clone(), def.clone());
+                pms
+            }),
+            op: self.op.clone().or(other.op),
         }
     }
 
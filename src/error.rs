@@ -54,9 +54,11 @@ pub enum Error {
     #[error("subprocess exited with code {code}")]
     CmdStatusCodeError { code: StatusCode, output: Output },
 
-    /// A [`Cmd`](crate::exec::Cmd) was interrupted by a signal.
-    #[error("subprocess interrupted by signal")]
-    CmdInterruptedError,
+    /// A [`Cmd`](crate::exec::Cmd) was interrupted by a signal, e.g. by
+    /// `Ctrl-C`.
+    #[allow(missing_docs)]
+    #[error("subprocess interrupted by signal while running `{op}`")]
+    CmdInterruptedError { op: String },
 
     /// Error while converting a [`Vec<u8>`] to a [`String`].
     #[error(transparent)]
@@ -66,10 +68,65 @@ pub enum Error {
     #[error(transparent)]
     DialogError(#[from] dialoguer::Error),
 
+    /// Error while serializing or deserializing JSON.
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+
     /// A non-specific [`io::Error`].
     #[error(transparent)]
     IoError(#[from] io::Error),
 
+    /// A package manager failed to find some packages.
+    #[allow(missing_docs)]
+    #[error("package not found")]
+    PackageNotFoundError { code: StatusCode, output: Output },
+
+    /// A package manager failed to lock its database, which is held by
+    /// another process.
+    #[allow(missing_docs)]
+    #[error("package database locked by another process")]
+    LockHeldError { code: StatusCode, output: Output },
+
+    /// The lock of a package manager has been held by another process for
+    /// longer than `--wait-lock` allows.
+    #[allow(missing_docs)]
+    #[error("timed out waiting for `{path}` held by {holder}")]
+    LockTimeoutError { path: String, holder: String },
+
+    /// A package manager failed to reach the network.
+    #[allow(missing_docs)]
+    #[error("network failure")]
+    NetworkError { code: StatusCode, output: Output },
+
+    /// A package manager ran out of disk space.
+    #[allow(missing_docs)]
+    #[error("no space left on device")]
+    DiskFullError { code: StatusCode, output: Output },
+
+    /// A package manager lacked the privileges for an operation.
+    #[allow(missing_docs)]
+    #[error("permission denied")]
+    PermissionDeniedError { code: StatusCode, output: Output },
+
+    /// A package manager refused to install packages conflicting with each
+    /// other or with the installed ones.
+    #[allow(missing_docs)]
+    #[error("conflicting packages")]
+    PackageConflictError { code: StatusCode, output: Output },
+
+    /// Some dependencies checked by `-T` are unsatisfied.
+    #[error("number of unsatisfied dependencies: {0}")]
+    UnsatisfiedDepsError(usize),
+
+    /// Some packages reported by `--outdated-report` are out of date.
+    #[error("number of pending upgrades: {0}")]
+    PendingUpgradesError(usize),
+
+    /// Some package managers failed to report their pending upgrades to
+    /// `--outdated-report`, or cannot list them at all.
+    #[error("number of package managers failing to report or unchecked: {0}")]
+    IncompleteReportError(usize),
+
     /// A [`Pm`](crate::pm::Pm) operation is not implemented.
     #[allow(missing_docs)]
     #[error("operation `{op}` is unimplemented for `{pm}`")]
@@ -80,9 +137,139 @@ pub enum Error {
     OtherError(String),
 }
 
+impl Error {
+    /// Returns the exit code of [`pacaptr`](crate) on this error.
+    ///
+    /// The failures classified by [`Failure`] are given the stable codes of
+    /// `sysexits.h`, while a subprocess exiting with an unclassified error
+    /// passes its own code through.
+    #[must_use]
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    pub const fn exit_code(&self) -> u8 {
+        match self {
+            Self::CmdStatusCodeError { code, .. } => *code as u8,
+            // `EX_DATAERR`.
+            Self::PackageConflictError { .. } => 65,
+            // `EX_NOINPUT`.
+            Self::PackageNotFoundError { .. } => 66,
+            // `EX_UNAVAILABLE`.
+            Self::NetworkError { .. } => 69,
+            // `EX_IOERR`.
+            Self::DiskFullError { .. } => 74,
+            // `EX_TEMPFAIL`.
+            Self::LockHeldError { .. } | Self::LockTimeoutError { .. } => 75,
+            // `EX_NOPERM`.
+            Self::PermissionDeniedError { .. } => 77,
+            // Same as `pacman -T`.
+            Self::UnsatisfiedDepsError(_) => 127,
+            // Same as `dnf check-update`.
+            Self::PendingUpgradesError(_) => 100,
+            // Set apart from the pending upgrades, which it takes precedence
+            // over.
+            Self::IncompleteReportError(_) => 101,
+            _ => 1,
+        }
+    }
+
+    /// Returns a hint on how to recover from this error, if any.
+    #[must_use]
+    pub const fn hint(&self) -> Option<&'static str> {
+        Some(match self {
+            Self::PackageNotFoundError { .. } => {
+                "check the package names, or refresh the package database with `pacaptr -Sy`"
+            }
+            Self::LockHeldError { .. } => {
+                "wait for the other package manager process to finish, \
+                 or remove the lock file if that process is gone"
+            }
+            Self::LockTimeoutError { .. } => {
+                "wait for the other process to finish, or raise the timeout of `--wait-lock`"
+            }
+            Self::NetworkError { .. } => {
+                "check your network connection, as well as the proxy and mirror settings"
+            }
+            Self::DiskFullError { .. } => {
+                "free up some disk space, e.g. by cleaning the package cache with `pacaptr -Scc`"
+            }
+            Self::PermissionDeniedError { .. } => {
+                "run the command again as root, or check the `elevate` config item"
+            }
+            Self::PackageConflictError { .. } => {
+                "remove the conflicting packages first, or pick another version"
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// The kinds of package manager failures that can be told apart by their
+/// output, each of which gives a more specific [`enum@Error`] than
+/// [`Error::CmdStatusCodeError`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Failure {
+    /// See [`Error::PackageNotFoundError`].
+    PackageNotFound,
+    /// See [`Error::LockHeldError`].
+    LockHeld,
+    /// See [`Error::NetworkError`].
+    Network,
+    /// See [`Error::DiskFullError`].
+    DiskFull,
+    /// See [`Error::PermissionDeniedError`].
+    PermissionDenied,
+    /// See [`Error::PackageConflictError`].
+    PackageConflict,
+}
+
+impl Failure {
+    /// The case-insensitive regex patterns recognizing the failures of any
+    /// package manager, tried after the ones given by
+    /// [`Pm::failures`](crate::pm::Pm::failures).
+    pub const GENERIC: &[(Self, &str)] = &[
+        (
+            Self::DiskFull,
+            r"no space left on device|not enough (free )?disk space",
+        ),
+        (
+            Self::PermissionDenied,
+            r"permission denied|operation not permitted|are you root\?",
+        ),
+        (
+            Self::LockHeld,
+            r"could not (get|acquire) lock|unable to lock database|is another process using it\?",
+        ),
+        (
+            Self::Network,
+            r"temporary failure (in name resolution|resolving)|could not resolve host|network is unreachable|connection (reset|timed out)|\b5\d\d\s+(internal server error|bad gateway|service unavailable|gateway time-?out)",
+        ),
+        (
+            Self::PackageConflict,
+            r"conflicting packages|conflicts with",
+        ),
+    ];
+
+    /// Makes the [`enum@Error`] of this failure, given the exit code and the
+    /// captured output of the subprocess.
+    #[must_use]
+    pub const fn into_error(self, code: StatusCode, output: Output) -> Error {
+        match self {
+            Self::PackageNotFound => Error::PackageNotFoundError { code, output },
+            Self::LockHeld => Error::LockHeldError { code, output },
+            Self::Network => Error::NetworkError { code, output },
+            Self::DiskFull => Error::DiskFullError { code, output },
+            Self::PermissionDenied => Error::PermissionDeniedError { code, output },
+            Self::PackageConflict => Error::PackageConflictError { code, output },
+        }
+    }
+}
+
 /// A simple [`enum@Error`] wrapper designed to be returned in the `main`
-/// function. It delegates its [`Debug`] implementation to the
-/// [`std::fmt::Display`] implementation of its underlying error.
+/// function.
+///
+/// It delegates its [`Debug`] implementation to the [`std::fmt::Display`]
+/// implementation of its underlying error, followed by the [`Error::hint`] if
+/// any.
 #[allow(clippy::module_name_repetitions)]
 pub struct MainError(Error);
 
@@ -94,19 +281,20 @@ impl From<Error> for MainError {
 
 impl Debug for MainError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // Erase the default "Error: " message header.
-        write!(f, "\r")?;
-        print::write_err(f, &*print::prompt::ERROR, self.0.as_report())
+        print::write_err(f, &*print::prompt::ERROR, self.0.as_report())?;
+        if let Some(hint) = self.0.hint() {
+            writeln!(f)?;
+            print::write_msg(f, &*print::prompt::HINT, hint)?;
+        }
+        Ok(())
     }
 }
 
 impl Termination for MainError {
+    /// Prints the error (with its hint, if any) to `stderr` and returns its
+    /// [`Error::exit_code`].
     fn report(self) -> ExitCode {
-        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
-        match self.0 {
-            Error::CmdStatusCodeError { code, .. } => code as u8,
-            _ => 1,
-        }
-        .into()
+        eprintln!("{self:?}");
+        self.0.exit_code().into()
     }
 }
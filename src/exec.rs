@@ -1,8 +1,13 @@
 //! APIs for spawning subprocesses and handling their results.
 
 use std::{
+    fmt,
     process::Stdio,
-    sync::atomic::{AtomicBool, Ordering},
+    sync::{
+        LazyLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
 };
 
 use bytes::{Bytes, BytesMut};
@@ -11,11 +16,14 @@ use futures::prelude::*;
 use indoc::indoc;
 use itertools::{Itertools, chain};
 use regex::{RegexSet, RegexSetBuilder};
+use serde::{Deserialize, Serialize};
 use tap::prelude::*;
 use tokio::{
     io::{self, AsyncRead, AsyncWrite},
-    process::Command as Exec,
+    process::{Child, Command as Exec},
+    sync::watch,
     task::JoinHandle,
+    time,
 };
 #[allow(clippy::wildcard_imports)]
 use tokio_util::{
@@ -58,6 +66,17 @@ pub enum Mode {
         quiet: bool,
     },
 
+    /// Prints out the command which should be executed, runs it attached to a
+    /// pseudo-terminal and collects its `stdout`/`stderr` combined.
+    ///
+    /// Unlike [`Mode::CheckAll`], this will work with a colored `stdout` and
+    /// with progress bars. Falls back to [`Mode::CheckErr`] where
+    /// pseudo-terminals are unavailable.
+    Pty {
+        /// Whether the log output should be suppressed.
+        quiet: bool,
+    },
+
     /// A CUSTOM prompt implemented by a `pacaptr` module itself.
     ///
     /// Prints out the command which should be executed, runs it and collects
@@ -77,13 +96,332 @@ pub type StatusCode = i32;
 /// This function might return one of the following errors:
 ///
 /// - [`Error::CmdStatusCodeError`], when `status` is `Some(n)` where `n != 0`.
-/// - [`Error::CmdInterruptedError`], when `status` is `None`.
-#[allow(clippy::missing_const_for_fn)]
-fn exit_result(code: Option<StatusCode>, output: Output) -> Result<Output> {
+/// - [`Error::CmdInterruptedError`] for the operation `op`, when `status` is
+///   `None`.
+fn exit_result(code: Option<StatusCode>, output: Output, op: String) -> Result<Output> {
     match code {
         Some(0) => Ok(output),
         Some(code) => Err(Error::CmdStatusCodeError { code, output }),
-        None => Err(Error::CmdInterruptedError),
+        None => Err(Error::CmdInterruptedError { op }),
+    }
+}
+
+/// The number of interruptions (e.g. `Ctrl-C`) received so far, as recorded by
+/// [`interrupt`].
+static INTERRUPTS: LazyLock<watch::Sender<usize>> = LazyLock::new(|| watch::channel(0).0);
+
+tokio::task_local! {
+    /// The interruptions recorded in place of [`INTERRUPTS`] within the
+    /// current task, as set up by [`scope_interrupts`].
+    static SCOPED_INTERRUPTS: watch::Sender<usize>;
+}
+
+/// The time given to a [`Cmd`] to exit after the first interruption, before
+/// it is terminated.
+const INTERRUPT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// The time given to a [`Cmd`] to exit after it has been terminated, before it
+/// is killed.
+const TERMINATE_GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Records an interruption (e.g. `Ctrl-C`), to be handled by the running
+/// [`Cmd`]s.
+///
+/// At the first interruption, the running [`Cmd`]s are sent a `SIGINT` (on
+/// Unix) unless they have got it from the terminal already, and are terminated
+/// if they are still running after a grace period. At the second one, they are
+/// terminated right away.
+pub fn interrupt() {
+    let record = |interrupts: &watch::Sender<usize>| interrupts.send_modify(|n| *n += 1);
+    SCOPED_INTERRUPTS
+        .try_with(record)
+        .unwrap_or_else(|_| record(&INTERRUPTS));
+}
+
+/// Subscribes to the interruptions recorded by [`interrupt`], for the waits
+/// outside of a [`Cmd`] to be cut short as well.
+pub(crate) fn interrupts() -> watch::Receiver<usize> {
+    SCOPED_INTERRUPTS
+        .try_with(watch::Sender::subscribe)
+        .unwrap_or_else(|_| INTERRUPTS.subscribe())
+}
+
+/// Runs `fut` with the interruptions recorded by [`interrupt`] kept apart
+/// from those of the other tasks, e.g. to run the tests concurrently.
+#[cfg(feature = "test")]
+pub async fn scope_interrupts<F: Future>(fut: F) -> F::Output {
+    SCOPED_INTERRUPTS.scope(watch::channel(0).0, fut).await
+}
+
+/// Waits for `child` to exit and returns its exit status code, handling the
+/// interruptions received from `interrupts` in the meantime.
+///
+/// Returns `None` if the child has been interrupted.
+async fn wait_interruptible(
+    mut child: Child,
+    mut interrupts: watch::Receiver<usize>,
+) -> Result<Option<StatusCode>> {
+    let process = Process::of(&child);
+    let seen = *interrupts.borrow_and_update();
+    tokio::select! {
+        status = child.wait() => return Ok(status.map_err(Error::CmdWaitError)?.code()),
+        _ = interrupts.changed() => (),
+    }
+    process.interrupt();
+    // The second interruption might have been recorded along with the first.
+    if *interrupts.borrow_and_update() < seen + 2 {
+        tokio::select! {
+            _ = child.wait() => return Ok(None),
+            _ = interrupts.changed() => (),
+            () = time::sleep(INTERRUPT_GRACE_PERIOD) => (),
+        }
+    }
+    process.terminate(&mut child).await;
+    Ok(None)
+}
+
+/// The process of a running [`Cmd`], to which the interruptions are
+/// forwarded.
+///
+/// The process is kept in the process group of `pacaptr`, so that it can still
+/// read from the terminal, and a `Ctrl-C` typed there reaches both `pacaptr`
+/// and the whole process tree of the [`Cmd`] (e.g. `sudo` running `apt`
+/// running `dpkg`).
+#[cfg(unix)]
+#[derive(Debug)]
+struct Process {
+    /// The PID of the process, if still running.
+    id: Option<nix::unistd::Pid>,
+}
+
+#[cfg(unix)]
+impl Process {
+    /// Returns the process of `child`.
+    fn of(child: &Child) -> Self {
+        let id = child
+            .id()
+            .and_then(|pid| i32::try_from(pid).ok())
+            .map(nix::unistd::Pid::from_raw);
+        Self { id }
+    }
+
+    /// Sends `signal` to the process.
+    fn signal(&self, signal: nix::sys::signal::Signal) {
+        if let Some(id) = self.id {
+            _ = nix::sys::signal::kill(id, signal);
+        }
+    }
+
+    /// Forwards an interruption to the process as a `SIGINT`, unless it has
+    /// been typed at the terminal, of which the process has got its own
+    /// `SIGINT` already.
+    fn interrupt(&self) {
+        use nix::unistd::{getpgrp, tcgetpgrp};
+
+        let typed = tcgetpgrp(std::io::stdin()).is_ok_and(|fg| fg == getpgrp());
+        if !typed {
+            self.signal(nix::sys::signal::Signal::SIGINT);
+        }
+    }
+
+    /// Terminates `child` with a `SIGTERM`, followed by a `SIGKILL` if it is
+    /// still running after a grace period.
+    async fn terminate(&self, child: &mut Child) {
+        self.signal(nix::sys::signal::Signal::SIGTERM);
+        tokio::select! {
+            _ = child.wait() => return,
+            () = time::sleep(TERMINATE_GRACE_PERIOD) => (),
+        }
+        // The child might have exited in the meantime, in which case there is
+        // nothing left to kill.
+        _ = child.kill().await;
+    }
+}
+
+/// Resizes the pseudo-terminal whose master side is `master` to the size of
+/// the current terminal whenever the latter is resized, i.e. on `SIGWINCH`.
+#[cfg(unix)]
+async fn forward_winsize(master: std::os::fd::OwnedFd) {
+    use rustix::termios::{Winsize, tcsetwinsize};
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let Ok(mut winch) = signal(SignalKind::window_change()) else {
+        return;
+    };
+    while winch.recv().await.is_some() {
+        if let Some((ws_row, ws_col)) = console::Term::stdout().size_checked() {
+            let winsize = Winsize {
+                ws_row,
+                ws_col,
+                ws_xpixel: 0,
+                ws_ypixel: 0,
+            };
+            _ = tcsetwinsize(&master, winsize);
+        }
+    }
+}
+
+/// The process of a running [`Cmd`].
+#[cfg(windows)]
+#[derive(Debug)]
+struct Process;
+
+#[cfg(windows)]
+impl Process {
+    /// Returns the process of `child`.
+    const fn of(_child: &Child) -> Self {
+        Self
+    }
+
+    /// Forwards an interruption to the process.
+    ///
+    /// On Windows, `Ctrl-C` has already been received by every process
+    /// attached to the console, so there is nothing left to do.
+    #[allow(clippy::unused_self)]
+    const fn interrupt(&self) {}
+
+    /// Kills `child`.
+    #[allow(clippy::unused_self)]
+    async fn terminate(&self, child: &mut Child) {
+        // The child might have exited in the meantime, in which case there is
+        // nothing left to kill.
+        _ = child.kill().await;
+    }
+}
+
+/// The way in which a [`Cmd`] gains the privileges of a **normal admin**.
+///
+/// In the config, this is written as `"sudo"`, `"doas"`, `"run0"`, `"pkexec"`,
+/// `"none"`, or as a custom command template such as `["su", "-c", "{}"]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RawElevate", into = "RawElevate")]
+pub enum Elevate {
+    /// Uses `sudo -S`.
+    #[default]
+    Sudo,
+
+    /// Uses `doas`.
+    Doas,
+
+    /// Uses `run0` from `systemd`.
+    Run0,
+
+    /// Uses `pkexec` from `polkit`.
+    Pkexec,
+
+    /// Uses a custom command, to which the command line is appended, unless
+    /// one of its arguments is `{}`, which is then replaced by the whole
+    /// command line, e.g. `["su", "-c", "{}"]`.
+    Custom(Vec<String>),
+
+    /// Runs the command as is.
+    None,
+}
+
+/// The serialized form of an [`Elevate`].
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawElevate {
+    Name(String),
+    Custom(Vec<String>),
+}
+
+impl TryFrom<RawElevate> for Elevate {
+    type Error = String;
+
+    fn try_from(raw: RawElevate) -> Result<Self, Self::Error> {
+        match raw {
+            RawElevate::Name(name) => match name.as_str() {
+                "sudo" => Ok(Self::Sudo),
+                "doas" => Ok(Self::Doas),
+                "run0" => Ok(Self::Run0),
+                "pkexec" => Ok(Self::Pkexec),
+                "none" => Ok(Self::None),
+                _ => Err(format!(
+                    "unknown `elevate` value `{name}`, expected one of `sudo`, `doas`, \
+                     `run0`, `pkexec`, `none` or a custom command template"
+                )),
+            },
+            RawElevate::Custom(cmd) if cmd.is_empty() => {
+                Err("empty custom `elevate` command template".into())
+            }
+            RawElevate::Custom(cmd) => Ok(Self::Custom(cmd)),
+        }
+    }
+}
+
+impl From<Elevate> for RawElevate {
+    fn from(elevate: Elevate) -> Self {
+        let name = match elevate {
+            Elevate::Sudo => "sudo",
+            Elevate::Doas => "doas",
+            Elevate::Run0 => "run0",
+            Elevate::Pkexec => "pkexec",
+            Elevate::Custom(cmd) => return Self::Custom(cmd),
+            Elevate::None => "none",
+        };
+        Self::Name(name.into())
+    }
+}
+
+impl Elevate {
+    /// The placeholder for the command line in an [`Elevate::Custom`]
+    /// template.
+    const PLACEHOLDER: &str = "{}";
+
+    /// Detects the first of `sudo`, `doas`, `run0` and `pkexec` available in
+    /// `$PATH`, or returns [`Elevate::None`] if none of them is found.
+    ///
+    /// The result is cached, so `$PATH` is only looked up once.
+    #[must_use]
+    pub fn detect() -> Self {
+        static DETECTED: LazyLock<Elevate> = LazyLock::new(|| {
+            [
+                ("sudo", Elevate::Sudo),
+                ("doas", Elevate::Doas),
+                ("run0", Elevate::Run0),
+                ("pkexec", Elevate::Pkexec),
+            ]
+            .into_iter()
+            .find_map(|(name, elevate)| is_exe(name, "").then_some(elevate))
+            .unwrap_or(Elevate::None)
+        });
+        DETECTED.clone()
+    }
+
+    /// Returns the command prefix used to gain the privileges, e.g.
+    /// `["sudo", "-S"]`.
+    fn prefix(&self) -> Vec<&str> {
+        match self {
+            Self::Sudo => vec!["sudo", "-S"],
+            Self::Doas => vec!["doas"],
+            Self::Run0 => vec!["run0"],
+            Self::Pkexec => vec!["pkexec"],
+            Self::Custom(cmd) => cmd.iter().map(AsRef::as_ref).collect(),
+            Self::None => vec![],
+        }
+    }
+
+    /// Wraps the command line `argv` so that it runs with the privileges.
+    ///
+    /// `show` renders the whole command line as a single argument, as done by
+    /// an [`Elevate::Custom`] template containing `{}`.
+    fn wrap(&self, argv: Vec<String>, show: impl FnOnce(&[String]) -> String) -> Vec<String> {
+        let prefix = self.prefix();
+        if !prefix.contains(&Self::PLACEHOLDER) {
+            return chain!(prefix.into_iter().map(Into::into), argv).collect();
+        }
+        let line = show(&argv);
+        prefix
+            .into_iter()
+            .map(|arg| {
+                if arg == Self::PLACEHOLDER {
+                    line.clone()
+                } else {
+                    arg.into()
+                }
+            })
+            .collect()
     }
 }
 
@@ -99,6 +437,10 @@ pub struct Cmd {
     /// `sudo`.
     pub sudo: bool,
 
+    /// The way in which the privileges are gained if [`sudo`](field@Cmd::sudo)
+    /// is set, as given by [`Config::elevate`](crate::config::Config::elevate).
+    pub elevate: Elevate,
+
     /// The "command" part of the command string, e.g. `brew install`.
     pub cmd: Vec<String>,
 
@@ -107,6 +449,10 @@ pub struct Cmd {
 
     /// The "keywords" part of the command string, e.g. `curl fish`.
     pub kws: Vec<String>,
+
+    /// The part of the command string following the keywords, if any, e.g.
+    /// `--force-depends` in `opkg install curl --force-depends`.
+    pub tail: Vec<String>,
 }
 
 impl Cmd {
@@ -136,13 +482,26 @@ impl Cmd {
         self
     }
 
+    /// Overrides the value of [`tail`](field@Cmd::tail).
+    pub(crate) fn tail(mut self, tail: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        self.tail = tail.into_iter().map(|s| s.as_ref().into()).collect();
+        self
+    }
+
     /// Overrides the value of [`sudo`](field@Cmd::sudo).
     pub(crate) const fn sudo(mut self, sudo: bool) -> Self {
         self.sudo = sudo;
         self
     }
 
-    /// Determines if this command actually needs to run with `sudo -S`.
+    /// Overrides the value of [`elevate`](field@Cmd::elevate).
+    pub(crate) fn elevate(mut self, elevate: Elevate) -> Self {
+        self.elevate = elevate;
+        self
+    }
+
+    /// Determines if this command actually needs to run with elevated
+    /// privileges, e.g. with `sudo -S`.
     ///
     /// If a **normal admin** needs to run it with `sudo`, and we are not
     /// `root`, then this is the case.
@@ -151,30 +510,51 @@ impl Cmd {
         self.sudo && !is_root()
     }
 
-    /// Converts a [`Cmd`] object into an [`Exec`].
-    #[must_use]
-    fn build(self) -> Exec {
+    /// Returns the arguments of this command line, elevated if needed, with
+    /// `show` rendering a whole command line as a single argument.
+    fn argv(&self, show: impl FnOnce(&[String]) -> String) -> Vec<String> {
         // ! Special fix for `zypper`: `zypper install -y curl` is accepted,
         // ! but not `zypper install curl -y`.
         // ! So we place the flags first, and then keywords.
+        let argv = chain!(&self.cmd, &self.flags, &self.kws, &self.tail)
+            .cloned()
+            .collect_vec();
         if self.should_sudo() {
-            Exec::new("sudo").tap_mut(|builder| {
-                builder
-                    .arg("-S")
-                    .args(&self.cmd)
-                    .args(&self.flags)
-                    .args(&self.kws);
-            })
+            self.elevate.wrap(argv, show)
         } else {
-            let (cmd, subcmd) = self
-                .cmd
-                .split_first()
-                .expect("failed to build Cmd, command is empty");
-            Exec::new(cmd).tap_mut(|builder| {
-                builder.args(subcmd).args(&self.flags).args(&self.kws);
-            })
+            argv
         }
     }
+
+    /// Converts a [`Cmd`] object into an [`Exec`].
+    #[must_use]
+    fn build(self) -> Exec {
+        let argv = self.argv(|argv| shell_words(argv).join(" "));
+        let (cmd, subcmd) = argv
+            .split_first()
+            .expect("failed to build Cmd, command is empty");
+        Exec::new(cmd).tap_mut(|builder| {
+            builder.args(subcmd);
+        })
+    }
+}
+
+/// Quotes the given arguments for a POSIX shell where needed, as passed to
+/// [`Elevate::Custom`] templates such as `["su", "-c", "{}"]`.
+fn shell_words(argv: &[String]) -> Vec<String> {
+    argv.iter()
+        .map(|arg| {
+            if !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./=:+,@%".contains(c))
+            {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', r"'\''"))
+            }
+        })
+        .collect()
 }
 
 /// Takes contents from an input stream and copy to an output stream (optional)
@@ -204,6 +584,38 @@ async fn exec_tee(
     Ok(buf)
 }
 
+/// Waits for `child` to exit while copying its output from `src` to `out`
+/// (optional), and returns the output according to the exit status of
+/// `child`, which is running the operation `op`.
+///
+/// Helper to implement [`Cmd::exec_check_output`] and [`Cmd::exec_pty`].
+async fn collect_output(
+    child: Child,
+    src: impl Stream<Item = io::Result<Bytes>> + Send,
+    out: Option<impl AsyncWrite + Send>,
+    op: String,
+) -> Result<Output> {
+    let mut code: JoinHandle<Result<Option<i32>>> =
+        tokio::spawn(wait_interruptible(child, interrupts()));
+
+    let tee = exec_tee(src, out);
+    tokio::pin!(tee);
+    let code = tokio::select! {
+        output = &mut tee => {
+            let code = code.await.map_err(Error::CmdJoinError)??;
+            return exit_result(code, output?, op);
+        }
+        code = &mut code => code.map_err(Error::CmdJoinError)??,
+    };
+    // Once interrupted, the pipes are not waited for, as they might have
+    // been inherited by the grandchildren still running.
+    let output = match code {
+        Some(_) => tee.await?,
+        None => Output::default(),
+    };
+    exit_result(code, output, op)
+}
+
 macro_rules! docs_errors_exec {
     () => {
         indoc! {"
@@ -245,6 +657,12 @@ impl Cmd {
                 }
                 self.exec_checkerr(false).await
             }
+            Mode::Pty { quiet } => {
+                if !quiet {
+                    println_quoted(&*prompt::RUNNING, &self);
+                }
+                self.exec_pty(false).await
+            }
             Mode::Prompt => self.exec_prompt(false).await,
         }
     }
@@ -253,7 +671,7 @@ impl Cmd {
     /// and [`Cmd::exec_checkall`] (otherwise).
     #[doc = docs_errors_exec!()]
     async fn exec_check_output(self, mute: bool, merge: bool) -> Result<Output> {
-        use Error::{CmdJoinError, CmdNoHandleError, CmdSpawnError, CmdWaitError};
+        use Error::{CmdNoHandleError, CmdSpawnError};
         use tokio_stream::StreamExt;
 
         fn make_reader(
@@ -265,6 +683,7 @@ impl Cmd {
             })
         }
 
+        let op = self.cmd.join(" ");
         let mut child = self
             .build()
             .stderr(Stdio::piped())
@@ -290,14 +709,7 @@ impl Cmd {
             Either::Right(io::stderr())
         };
 
-        let code: JoinHandle<Result<Option<i32>>> = tokio::spawn(async move {
-            let status = child.wait().await.map_err(CmdWaitError)?;
-            Ok(status.code())
-        });
-
-        let output = exec_tee(&mut reader, (!mute).then_some(&mut out)).await?;
-        let code = code.await.map_err(CmdJoinError)??;
-        exit_result(code, output)
+        collect_output(child, &mut reader, (!mute).then_some(&mut out), op).await
     }
 
     /// Executes a [`Cmd`] and returns its `stdout` and `stderr`.
@@ -318,6 +730,75 @@ impl Cmd {
         self.exec_check_output(mute, false).await
     }
 
+    /// Executes a [`Cmd`] attached to a pseudo-terminal, and returns its
+    /// `stdout` and `stderr` combined, as seen on the terminal.
+    ///
+    /// If `mute` is `false`, then the output will be printed to `stdout` too,
+    /// keeping the colors and the progress bars of the command.
+    ///
+    /// This falls back to [`exec_checkerr`](Cmd::exec_checkerr) if no
+    /// pseudo-terminal can be opened.
+    #[cfg(unix)]
+    #[doc = docs_errors_exec!()]
+    async fn exec_pty(self, mute: bool) -> Result<Output> {
+        use nix::{
+            errno::Errno,
+            pty::{Winsize, openpty},
+            sys::termios::{self, OutputFlags, SetArg},
+        };
+
+        // The pseudo-terminal is given the size of the current one, if any, and
+        // is resized along with it (see `forward_winsize`).
+        let winsize = console::Term::stdout()
+            .size_checked()
+            .map(|(ws_row, ws_col)| Winsize {
+                ws_row,
+                ws_col,
+                ws_xpixel: 0,
+                ws_ypixel: 0,
+            });
+        let Ok(pty) = openpty(winsize.as_ref(), None) else {
+            return self.exec_checkerr(mute).await;
+        };
+        // Newlines are kept as is, to be translated by the current terminal if
+        // needed.
+        if let Ok(mut termios) = termios::tcgetattr(&pty.slave) {
+            termios.output_flags.remove(OutputFlags::ONLCR);
+            _ = termios::tcsetattr(&pty.slave, SetArg::TCSANOW, &termios);
+        }
+
+        // `stdin` is left untouched, so that the prompts of the command can
+        // still be answered.
+        let op = self.cmd.join(" ");
+        let child = self
+            .build()
+            .stdout(pty.slave.try_clone()?)
+            .stderr(pty.slave)
+            .spawn()
+            .map_err(Error::CmdSpawnError)?;
+
+        let resize = tokio::spawn(forward_winsize(pty.master.try_clone()?));
+
+        // Reading from the master side fails with `EIO` once all the slave
+        // sides have been closed, which simply marks the end of the output.
+        let master = tokio::fs::File::from_std(pty.master.into());
+        let reader = into_bytes(master).take_while(|res| {
+            let eio = matches!(res, Err(e) if e.raw_os_error() == Some(Errno::EIO as i32));
+            future::ready(!eio)
+        });
+        let res = collect_output(child, reader, (!mute).then(io::stdout), op).await;
+        resize.abort();
+        res
+    }
+
+    /// Executes a [`Cmd`] like [`exec_pty`](Cmd::exec_pty) on Unix, which
+    /// falls back to [`exec_checkerr`](Cmd::exec_checkerr) elsewhere.
+    #[cfg(not(unix))]
+    #[doc = docs_errors_exec!()]
+    async fn exec_pty(self, mute: bool) -> Result<Output> {
+        self.exec_checkerr(mute).await
+    }
+
     /// Executes a [`Cmd`] and collects its `stderr`.
     ///
     /// If `mute` is `false`, then its `stderr` output will be printed to
@@ -365,14 +846,25 @@ impl Cmd {
     }
 }
 
-impl std::fmt::Display for Cmd {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let sudo: &str = if self.should_sudo() { "sudo -S " } else { "" };
-        let cmd = chain!(&self.cmd, &self.flags, &self.kws).join(" ");
-        write!(f, "{sudo}{cmd}")
+impl fmt::Display for Cmd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let argv = self.argv(|argv| {
+            let line = shell_words(argv).join(" ");
+            shell_words(&[line]).remove(0)
+        });
+        write!(f, "{}", argv.join(" "))
     }
 }
 
+/// Asks the user if (s)he wishes to proceed with the given action, e.g.
+/// `with the undo plan?`, in the same way as [`Mode::Prompt`].
+///
+/// # Errors
+/// Returns an [`Error::DialogError`] if the prompt fails.
+pub fn confirm(action: &str) -> Result<bool> {
+    Ok(prompt("Proceed", action, &["Yes", "No"])? == 0)
+}
+
 /// Gives a prompt and returns the index of the user choice.
 fn prompt(prompt: &str, question: &str, expected: &[&str]) -> Result<usize> {
     Ok(FuzzySelect::with_theme(&question_theme(prompt))
@@ -398,16 +890,22 @@ macro_rules! docs_errors_grep {
 /// An error message will be returned if this is not the case.
 #[doc = docs_errors_grep!()]
 pub fn grep<'t>(text: &'t str, patterns: &[&str]) -> Result<Vec<&'t str>> {
-    let patterns: RegexSet = RegexSetBuilder::new(patterns)
-        .case_insensitive(true)
-        .build()
-        .map_err(|e| Error::OtherError(format!("ill-formed patterns found: {e:?}")))?;
+    let patterns = grep_patterns(patterns)?;
     Ok(text
         .lines()
         .filter(|line| patterns.matches(line).into_iter().count() == patterns.len())
         .collect())
 }
 
+/// Builds the case-insensitive [`RegexSet`] used by [`grep`].
+#[doc = docs_errors_grep!()]
+pub fn grep_patterns(patterns: &[&str]) -> Result<RegexSet> {
+    RegexSetBuilder::new(patterns)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| Error::OtherError(format!("ill-formed patterns found: {e:?}")))
+}
+
 /// Prints the result of [`grep`] line by line.
 #[doc = docs_errors_grep!()]
 pub fn grep_print(text: &str, patterns: &[&str]) -> Result<()> {
@@ -430,6 +928,40 @@ pub fn grep_print_with_header(text: &str, patterns: &[&str], header_lines: usize
     Ok(())
 }
 
+/// Reduces the package listing in `text` to bare package names, one per line.
+///
+/// Only the first word of each unindented line is kept, with any trailing
+/// colon and any leading `repo/` prefix stripped, so that e.g. both `bash 5.2`
+/// and `core/bash 5.2 [installed]` become `bash`. Absolute paths are kept
+/// as-is.
+pub fn package_names(text: &str) -> impl Iterator<Item = &str> {
+    text.lines()
+        .filter(|ln| !ln.starts_with(char::is_whitespace))
+        .filter_map(|ln| {
+            let word = ln.split_whitespace().next()?.trim_end_matches(':');
+            let name = match word.rsplit_once('/') {
+                Some((_, name)) if !word.starts_with('/') => name,
+                _ => word,
+            };
+            (!name.is_empty()).then_some(name)
+        })
+}
+
+/// Escapes a package name so that it can be matched literally in a `sed`
+/// address, either as a basic or an extended regular expression.
+#[must_use]
+pub fn sed_escape(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '-') {
+                c.to_string()
+            } else {
+                format!("[{c}]")
+            }
+        })
+        .collect()
+}
+
 /// Checks if an executable exists by name (consult `$PATH`) or by path.
 ///
 /// To check by one parameter only, pass `""` to the other one.
@@ -458,3 +990,26 @@ pub fn is_root() -> bool {
 fn into_bytes(reader: impl AsyncRead) -> impl Stream<Item = io::Result<Bytes>> {
     FramedRead::new(reader, BytesCodec::new()).map_ok(BytesMut::freeze)
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn pty() {
+        let cmd = |script: &str| Cmd::new(["sh", "-c", script]);
+
+        // The end of the output, marked by `EIO`, is not an error.
+        let out = cmd("test -t 1 && echo tty")
+            .exec(Mode::Pty { quiet: true })
+            .await
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "tty\n");
+
+        let err = cmd("test -t 1 && exit 3")
+            .exec(Mode::Pty { quiet: true })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::CmdStatusCodeError { code: 3, .. }));
+    }
+}
@@ -0,0 +1,351 @@
+//! APIs for the append-only log of the operations performed by
+//! [`pacaptr`](crate), in the spirit of `/var/log/pacman.log`.
+//!
+//! The log is kept in `$XDG_STATE_HOME/pacaptr/history.jsonl` (or
+//! `$HOME/.local/state/pacaptr/history.jsonl` if `$XDG_STATE_HOME` is not set),
+//! with one JSON [`Entry`] on each line.
+
+use std::{
+    env, fmt,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    ops,
+    path::PathBuf,
+};
+
+use jiff::{Timestamp, Zoned, civil::Date, tz::TimeZone};
+use serde::{Deserialize, Serialize};
+use tap::prelude::*;
+
+use crate::{
+    error::{Error, Result},
+    exec::Cmd,
+};
+
+/// The crate name.
+const CRATE_NAME: &str = clap::crate_name!();
+
+/// The name of the history file.
+const HISTORY_FILE: &str = "history.jsonl";
+
+tokio::task_local! {
+    /// The history file used in place of the one given by [`path`] within the
+    /// current task, as set up by `scope_path`.
+    static SCOPED_PATH: PathBuf;
+}
+
+/// A command recorded in the history log.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// When the command finished.
+    pub time: Timestamp,
+
+    /// The name of the package manager, e.g. `apt`.
+    pub pm: String,
+
+    /// The `pacman` operation being performed, e.g. `Suy`, or `undo` for a
+    /// native `--undo`.
+    pub op: String,
+
+    /// The keywords passed to the command.
+    pub kws: Vec<String>,
+
+    /// The keywords of an installation that were already installed before the
+    /// command, and thus are left alone by `--undo`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub installed: Vec<String>,
+
+    /// Whether the keywords of an installation that were already installed
+    /// before the command are unknown, so that it cannot be reverted safely.
+    #[serde(default, skip_serializing_if = "ops::Not::not")]
+    pub irreversible: bool,
+
+    /// The command executed.
+    pub cmd: String,
+
+    /// The exit status of the command (`127` if it has failed to start), or
+    /// `None` if it has been interrupted.
+    pub status: Option<i32>,
+
+    /// Whether the command has only been printed as a part of a dry run.
+    pub dry_run: bool,
+}
+
+impl Entry {
+    /// Makes a new [`Entry`] timestamped with the current time.
+    #[must_use]
+    pub fn new(pm: &str, op: &str, cmd: &Cmd, status: Option<i32>, dry_run: bool) -> Self {
+        Self {
+            time: Timestamp::now(),
+            pm: pm.into(),
+            op: op.into(),
+            kws: cmd.kws.clone(),
+            installed: vec![],
+            irreversible: false,
+            cmd: cmd.to_string(),
+            status,
+            dry_run,
+        }
+    }
+
+    /// Sets the [`installed`](field@Entry::installed) keywords of this entry,
+    /// or marks it as [`irreversible`](field@Entry::irreversible) if they are
+    /// unknown.
+    #[must_use]
+    pub fn installed(mut self, installed: Option<Vec<String>>) -> Self {
+        self.irreversible = installed.is_none();
+        self.installed = installed.unwrap_or_default();
+        self
+    }
+
+    /// Returns `true` if the given `pacman` operation (e.g. `S`) installs the
+    /// packages given as keywords, so that [`Entry::inverse_op`] removes them.
+    #[must_use]
+    pub fn is_install(op: &str) -> bool {
+        matches!(op, "S" | "Sy")
+    }
+
+    /// Returns the `pacman` operation reverting this entry, i.e. `R` for an
+    /// installation and `S` for a removal, or `None` if it cannot be reverted.
+    #[must_use]
+    pub fn inverse_op(&self) -> Option<&'static str> {
+        if self.dry_run || self.status != Some(0) || self.undo_kws().is_empty() {
+            return None;
+        }
+        match self.op.as_str() {
+            op if Self::is_install(op) => Some("R"),
+            op if op.starts_with('R') => Some("S"),
+            _ => None,
+        }
+    }
+
+    /// Returns the keywords to be passed to [`Entry::inverse_op`], i.e. all
+    /// the keywords but the [`installed`](field@Entry::installed) ones.
+    #[must_use]
+    pub fn undo_kws(&self) -> Vec<&str> {
+        self.kws
+            .iter()
+            .filter(|kw| !self.installed.contains(kw))
+            .map(AsRef::as_ref)
+            .collect()
+    }
+}
+
+impl fmt::Display for Entry {
+    /// Formats this entry in the style of `pacman.log`, e.g.
+    /// `[2024-05-01T12:34:56+0200] [apt] -S curl: apt install curl (exit 0)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = self.time.to_zoned(TimeZone::system());
+        write!(
+            f,
+            "[{}] [{}] -{}",
+            time.strftime("%Y-%m-%dT%H:%M:%S%z"),
+            self.pm,
+            self.op
+        )?;
+        for kw in &self.kws {
+            write!(f, " {kw}")?;
+        }
+        write!(f, ": {}", self.cmd)?;
+        if self.dry_run {
+            return write!(f, " (dry run)");
+        }
+        match self.status {
+            Some(code) => write!(f, " (exit {code})"),
+            None => write!(f, " (interrupted)"),
+        }
+    }
+}
+
+/// Returns `true` if the given `pacman` operation (e.g. `Suy`) might modify
+/// the system and thus should be recorded.
+#[must_use]
+pub fn is_mutating(op: &str) -> bool {
+    let op = op.to_lowercase();
+    match op.as_bytes() {
+        [b'd' | b'r' | b'u', ..] => true,
+        [b'f', rest @ ..] => rest.contains(&b'y'),
+        [b's', rest @ ..] => !rest.iter().any(|c| b"gils".contains(c)),
+        _ => false,
+    }
+}
+
+/// The history file path is defined with the following precedence:
+///
+/// - `$XDG_STATE_HOME/pacaptr/history.jsonl`, if `$XDG_STATE_HOME` is set;
+/// - `$HOME/.local/state/pacaptr/history.jsonl`.
+#[must_use]
+pub fn path() -> Option<PathBuf> {
+    if let Ok(path) = SCOPED_PATH.try_with(Clone::clone) {
+        return Some(path);
+    }
+    env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| dirs_next::home_dir().map(|p| p.join(".local").join("state")))
+        .tap_some_mut(|p| p.extend([CRATE_NAME, HISTORY_FILE]))
+}
+
+/// Runs `fut` with the history file at `path`, kept apart from the user's own
+/// history, e.g. to run the tests.
+#[cfg(feature = "test")]
+pub async fn scope_path<F: Future>(path: PathBuf, fut: F) -> F::Output {
+    SCOPED_PATH.scope(path, fut).await
+}
+
+/// Appends an [`Entry`] to the history file.
+///
+/// # Errors
+/// Returns an [`Error::IoError`] if the history file cannot be written.
+pub fn append(entry: &Entry) -> Result<()> {
+    let Some(path) = path() else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// Reads all the entries from the history file, oldest first.
+///
+/// Lines that cannot be parsed (e.g. those truncated by a crash) are skipped.
+///
+/// # Errors
+/// Returns an [`Error::IoError`] if the history file exists but cannot be
+/// read.
+pub fn read() -> Result<Vec<Entry>> {
+    let Some(path) = path() else {
+        return Ok(vec![]);
+    };
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(text
+        .lines()
+        .filter_map(|ln| serde_json::from_str(ln).ok())
+        .collect())
+}
+
+/// Parses the argument of `--since`, which is either a timestamp (e.g.
+/// `2024-05-01T12:00:00Z`), a date in the local time zone (e.g. `2024-05-01`),
+/// or a span of time before now (e.g. `3d`, `2 hours`).
+///
+/// # Errors
+/// Returns an [`Error::ArgParseError`] if `since` is in none of these formats.
+pub fn parse_since(since: &str) -> Result<Timestamp> {
+    let err = || Error::ArgParseError {
+        msg: format!("ill-formed time `{since}`, expected a timestamp, a date or a span"),
+    };
+    if let Ok(ts) = since.parse::<Timestamp>() {
+        return Ok(ts);
+    }
+    if let Ok(date) = since.parse::<Date>() {
+        return date
+            .to_zoned(TimeZone::system())
+            .map(|zdt| zdt.timestamp())
+            .map_err(|_| err());
+    }
+    let span = since.parse::<jiff::Span>().map_err(|_| err())?;
+    Zoned::now()
+        .checked_sub(span.abs())
+        .map(|zdt| zdt.timestamp())
+        .map_err(|_| err())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn install(installed: Option<&[&str]>) -> Entry {
+        let entry: Entry = serde_json::from_str(
+            r#"{"time":"2024-05-01T12:00:00Z","pm":"apt","op":"S","kws":["fish","curl"],"cmd":"apt install fish curl","status":0,"dry_run":false}"#,
+        )
+        .unwrap();
+        entry.installed(installed.map(|kws| kws.iter().map(|&kw| kw.into()).collect()))
+    }
+
+    #[test]
+    fn undo() {
+        let fresh = install(Some(&[]));
+        assert_eq!(fresh.inverse_op(), Some("R"));
+        assert_eq!(fresh.undo_kws(), ["fish", "curl"]);
+        assert!(!fresh.irreversible);
+
+        let partial = install(Some(&["curl"]));
+        assert_eq!(partial.inverse_op(), Some("R"));
+        assert_eq!(partial.undo_kws(), ["fish"]);
+
+        let reinstall = install(Some(&["curl", "fish"]));
+        assert_eq!(reinstall.inverse_op(), None);
+
+        let unknown = install(None);
+        assert!(unknown.irreversible);
+        let line = serde_json::to_string(&unknown).unwrap();
+        assert!(line.contains(r#""irreversible":true"#));
+        assert_eq!(serde_json::from_str::<Entry>(&line).unwrap(), unknown);
+
+        let failed = Entry {
+            status: Some(100),
+            ..install(Some(&[]))
+        };
+        assert_eq!(failed.inverse_op(), None);
+        let dry = Entry {
+            dry_run: true,
+            ..install(Some(&[]))
+        };
+        assert_eq!(dry.inverse_op(), None);
+        let removal = Entry {
+            op: "Rns".into(),
+            ..install(Some(&[]))
+        };
+        assert_eq!(removal.inverse_op(), Some("S"));
+    }
+
+    #[test]
+    fn mutating() {
+        for op in ["S", "Sy", "Suy", "R", "Rns", "U", "D", "Fy", "Sc"] {
+            assert!(is_mutating(op), "{op}");
+        }
+        for op in ["Q", "Qi", "Ss", "Si", "Sl", "Sg", "F", "T"] {
+            assert!(!is_mutating(op), "{op}");
+        }
+    }
+
+    #[test]
+    fn since() {
+        let ts = parse_since("2024-05-01T12:00:00Z").unwrap();
+        assert_eq!(ts.to_string(), "2024-05-01T12:00:00Z");
+
+        let midnight = Date::constant(2024, 5, 1)
+            .to_zoned(TimeZone::system())
+            .unwrap()
+            .timestamp();
+        assert_eq!(parse_since("2024-05-01").unwrap(), midnight);
+
+        let now = Timestamp::now();
+        for span in ["3d", "3 days", "72h"] {
+            let ago = now.duration_since(parse_since(span).unwrap());
+            // The span is taken before now, with some leeway for the clock.
+            assert!(
+                (jiff::SignedDuration::from_hours(71)..=jiff::SignedDuration::from_hours(73))
+                    .contains(&ago),
+                "{span}: {ago:?}"
+            );
+        }
+
+        assert!(matches!(
+            parse_since("yesterday-ish"),
+            Err(Error::ArgParseError { .. })
+        ));
+    }
+}
@@ -21,5 +21,10 @@
 pub mod config;
 pub mod error;
 pub mod exec;
+pub mod history;
+pub mod lock;
+pub mod manifest;
+pub mod names;
+pub mod package;
 pub mod pm;
 pub mod print;
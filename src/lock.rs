@@ -0,0 +1,167 @@
+//! APIs for detecting the locks held by package managers on their databases,
+//! as used by `--wait-lock`.
+//!
+//! The holders of the locks taken with `fcntl` or `flock` are looked up in
+//! `/proc/locks`, so such locks can only be detected on Linux.
+
+use std::{fmt, fs, time::Duration};
+
+use tokio::time::{self, Instant};
+
+use crate::{
+    error::{Error, Result},
+    exec,
+    print::{println, prompt},
+};
+
+/// The interval between two checks of a [`Lock`] being waited for.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A lock taken by a package manager on its database.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lock {
+    /// A file locked with `fcntl` or `flock`, e.g.
+    /// `/var/lib/dpkg/lock-frontend`.
+    File(&'static str),
+
+    /// A file containing the PID of the process holding the lock, e.g.
+    /// `/var/run/zypp.pid`.
+    PidFile(&'static str),
+}
+
+/// The process holding a [`Lock`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Holder {
+    /// The PID of the process, if known.
+    pub pid: Option<i32>,
+
+    /// The command name of the process, e.g. `unattended-upgr`, if known.
+    pub name: Option<String>,
+}
+
+impl Holder {
+    fn new(pid: Option<i32>) -> Self {
+        let name = pid
+            .and_then(|pid| fs::read_to_string(format!("/proc/{pid}/comm")).ok())
+            .map(|name| name.trim().to_owned());
+        Self { pid, name }
+    }
+}
+
+impl fmt::Display for Holder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.pid, &self.name) {
+            (Some(pid), Some(name)) => write!(f, "process {pid} ({name})"),
+            (Some(pid), None) => write!(f, "process {pid}"),
+            (None, _) => write!(f, "another process"),
+        }
+    }
+}
+
+impl Lock {
+    /// Returns the path of the file behind this lock.
+    #[must_use]
+    pub const fn path(&self) -> &'static str {
+        match self {
+            Self::File(path) | Self::PidFile(path) => path,
+        }
+    }
+
+    /// Returns the process holding this lock, or `None` if the lock is free
+    /// (or if that cannot be told).
+    #[must_use]
+    pub fn holder(&self) -> Option<Holder> {
+        match self {
+            Self::File(path) => file_lock_holder(path),
+            Self::PidFile(path) => {
+                let pid = fs::read_to_string(path).ok()?.trim().parse().ok()?;
+                is_alive(pid).then(|| Holder::new(Some(pid)))
+            }
+        }
+    }
+}
+
+/// Returns the process holding a `fcntl` or `flock` lock on the file at
+/// `path`, as found in `/proc/locks`.
+#[cfg(target_os = "linux")]
+fn file_lock_holder(path: &str) -> Option<Holder> {
+    use std::os::unix::fs::MetadataExt;
+
+    let meta = fs::metadata(path).ok()?;
+    let dev = meta.dev();
+    // The `major` and `minor` macros of `glibc`.
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    let id = format!("{major:02x}:{minor:02x}:{}", meta.ino());
+
+    // Each line reads like `1: POSIX  ADVISORY  WRITE 1234 08:01:131090 0 EOF`,
+    // where the waiters are marked with `->` after the index.
+    fs::read_to_string("/proc/locks")
+        .ok()?
+        .lines()
+        .map(|ln| ln.split_whitespace().collect::<Vec<_>>())
+        .find(|fields| fields.get(1) != Some(&"->") && fields.get(5) == Some(&id.as_str()))
+        .map(|fields| Holder::new(fields[4].parse().ok().filter(|&pid| pid > 0)))
+}
+
+#[cfg(not(target_os = "linux"))]
+const fn file_lock_holder(_path: &str) -> Option<Holder> {
+    None
+}
+
+/// Returns `true` if the process `pid` is running.
+#[cfg(unix)]
+fn is_alive(pid: i32) -> bool {
+    use nix::{errno::Errno, sys::signal, unistd::Pid};
+
+    pid > 0 && !matches!(signal::kill(Pid::from_raw(pid), None), Err(Errno::ESRCH))
+}
+
+#[cfg(not(unix))]
+const fn is_alive(_pid: i32) -> bool {
+    false
+}
+
+/// Waits for all of the given `locks` to be released, checking them every
+/// second, before the `pacman` operation `op` (e.g. `S`) is performed.
+///
+/// # Errors
+/// Returns an [`Error::LockTimeoutError`] if any lock is still held after
+/// `timeout`, or an [`Error::CmdInterruptedError`] if interrupted in the
+/// meantime.
+pub async fn wait(locks: &[Lock], timeout: Duration, op: &str) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    let mut interrupts = exec::interrupts();
+    let mut last_held = None;
+    loop {
+        let Some((lock, holder)) = locks
+            .iter()
+            .find_map(|lock| lock.holder().map(|holder| (lock, holder)))
+        else {
+            return Ok(());
+        };
+        if Instant::now() >= deadline {
+            return Err(Error::LockTimeoutError {
+                path: lock.path().into(),
+                holder: holder.to_string(),
+            });
+        }
+        // Report each holder only once, however long the wait is.
+        let held = Some((*lock, holder.pid));
+        if held != last_held {
+            println(
+                &*prompt::PENDING,
+                format!("waiting for `{}` held by {holder}", lock.path()),
+            );
+            last_held = held;
+        }
+        tokio::select! {
+            () = time::sleep(POLL_INTERVAL.min(deadline - Instant::now())) => (),
+            _ = interrupts.changed() => {
+                return Err(Error::CmdInterruptedError {
+                    op: format!("-{op}"),
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,379 @@
+//! APIs for the declarative package manifests used by `pacaptr sync-manifest`.
+//!
+//! A manifest is a TOML file listing the packages to be installed, e.g.
+//!
+//! ```toml
+//! pm = "apt"
+//!
+//! [packages]
+//! curl = {}
+//! fish = ">=3.6"
+//! fd = { names = { apt = "fd-find", dnf = "fd-find" } }
+//! libyaml = { explicit = false }
+//! ```
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt, fs,
+    path::Path,
+};
+
+use figment::{
+    Figment,
+    providers::{Format, Toml},
+};
+use itertools::Itertools;
+use serde::Deserialize;
+
+use crate::{
+    error::{Error, Result},
+    package::{Dependency, Package},
+    pm::BoxPm,
+};
+
+/// A list of packages to be kept installed, as read from a TOML file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct Manifest {
+    /// The package manager this manifest has been exported from, if any.
+    ///
+    /// The versions in the manifest are ignored with other package managers.
+    pub pm: Option<String>,
+
+    /// The packages to be installed, indexed by name.
+    #[serde(default)]
+    pub packages: BTreeMap<String, Spec>,
+}
+
+/// The specification of a package in a [`Manifest`], written either as a table
+/// or as a bare version constraint.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(from = "RawSpec")]
+pub struct Spec {
+    /// The version constraint on the package in the syntax of `pacman -T`,
+    /// e.g. `>=3.6`, where a bare version means an exact one.
+    pub version: Option<String>,
+
+    /// Whether the package should be installed explicitly (rather than as a
+    /// dependency).
+    pub explicit: bool,
+
+    /// The names of the package with specific package managers, indexed by
+    /// the name of the latter, e.g. `apt = "fd-find"`.
+    pub names: HashMap<String, String>,
+}
+
+/// The serialized form of a [`Spec`].
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawSpec {
+    Version(String),
+    Table {
+        version: Option<String>,
+        #[serde(default = "explicit_default")]
+        explicit: bool,
+        #[serde(default)]
+        names: HashMap<String, String>,
+    },
+}
+
+const fn explicit_default() -> bool {
+    true
+}
+
+impl From<RawSpec> for Spec {
+    fn from(raw: RawSpec) -> Self {
+        match raw {
+            RawSpec::Version(version) => Self {
+                version: Some(version),
+                explicit: true,
+                names: HashMap::new(),
+            },
+            RawSpec::Table {
+                version,
+                explicit,
+                names,
+            } => Self {
+                version,
+                explicit,
+                names,
+            },
+        }
+    }
+}
+
+/// The operations needed to bring the system in line with a [`Manifest`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Plan {
+    /// The packages to be installed (or upgraded) explicitly.
+    pub install: Vec<String>,
+
+    /// The packages to be installed as dependencies.
+    pub install_deps: Vec<String>,
+
+    /// The explicitly installed packages missing from the manifest.
+    pub extra: Vec<String>,
+
+    /// The minimum versions to be checked with `-T` once the packages are
+    /// installed, since the package managers install the latest available
+    /// ones, e.g. `fish>=3.6`.
+    pub versions: Vec<String>,
+}
+
+impl Plan {
+    /// Returns `true` if there is nothing to install or remove.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.install.is_empty() && self.install_deps.is_empty() && self.extra.is_empty()
+    }
+}
+
+impl Manifest {
+    /// Makes a [`Manifest`] requiring at least the current versions of the
+    /// given packages installed with `pm`, as used by `--export`.
+    ///
+    /// The packages whose names are qualified with a repository, e.g.
+    /// `homebrew/cask/firefox`, are listed under their bare names, with the
+    /// qualified ones kept as the names specific to `pm`, unless another
+    /// package has the same bare name.
+    pub fn from_packages(pm: &str, pkgs: impl IntoIterator<Item = Package>) -> Self {
+        let pkgs = pkgs.into_iter().collect_vec();
+        let bare_counts = pkgs.iter().counts_by(|pkg| bare_name(&pkg.name).to_owned());
+        let packages = pkgs
+            .into_iter()
+            .map(|pkg| {
+                let bare = bare_name(&pkg.name);
+                // The categories of `emerge`, e.g. `app-shells/fish`, are a part
+                // of the package names.
+                let name = if pm == "emerge" || bare_counts[bare] > 1 {
+                    pkg.name.clone()
+                } else {
+                    bare.to_owned()
+                };
+                let names = (name != pkg.name)
+                    .then(|| (pm.to_owned(), pkg.name))
+                    .into_iter()
+                    .collect();
+                let spec = Spec {
+                    version: pkg.version.map(|version| format!(">={version}")),
+                    explicit: pkg.explicit.unwrap_or(true),
+                    names,
+                };
+                (name, spec)
+            })
+            .collect();
+        Self {
+            pm: Some(pm.into()),
+            packages,
+        }
+    }
+
+    /// Reads a [`Manifest`] from the given TOML file.
+    ///
+    /// # Errors
+    /// Returns an [`Error::IoError`] if the file cannot be read, or an
+    /// [`Error::ConfigError`] if it is ill-formed.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Ok(Figment::from(Toml::string(&text))
+            .extract()
+            .map_err(Box::new)?)
+    }
+
+    /// Compares this manifest against the explicitly installed packages
+    /// reported by `pm`, aka `Qe`, and returns the [`Plan`] to be carried out.
+    ///
+    /// # Errors
+    /// Returns an error if `pm` fails to list the installed packages, or an
+    /// [`Error::OtherError`] if a package to be installed is constrained by
+    /// anything other than a minimum version, which cannot be enforced.
+    pub async fn plan(&self, pm: &BoxPm<'_>, flags: &[&str]) -> Result<Plan> {
+        // Qualified names such as `homebrew/cask/firefox` also count as their
+        // bare ones.
+        let explicit: HashSet<_> = pm
+            .list_explicit(flags)
+            .await?
+            .into_iter()
+            .flat_map(|pkg| [bare_name(&pkg.name).to_owned(), pkg.name])
+            .collect();
+        let same_pm = self
+            .pm
+            .as_ref()
+            .is_none_or(|exported| exported == pm.name());
+        let mut plan = Plan::default();
+        let mut wanted = HashSet::new();
+        for (name, spec) in &self.packages {
+            let name = spec.names.get(pm.name()).unwrap_or(name);
+            wanted.extend([name.as_str(), bare_name(name)]);
+            let version = spec.version.as_deref().filter(|_| same_pm);
+            let dep = dependency(name, version)?;
+            let satisfied = match (spec.explicit, explicit.contains(name), version) {
+                (true, false, _) => false,
+                (true, true, None) => true,
+                _ => {
+                    let installed = pm.installed_version(bare_name(name), flags).await?;
+                    dep.is_satisfied_by(installed.as_deref())
+                }
+            };
+            if satisfied {
+                continue;
+            }
+            match &dep.constraint {
+                None => (),
+                Some((Ordering::Greater, ..)) => {
+                    plan.versions
+                        .push(format!("{}{}", dep.name, version.unwrap_or_default()));
+                }
+                Some(_) => {
+                    return Err(Error::OtherError(format!(
+                        "cannot install `{name}` with version `{}`: only minimum versions \
+                         (`>=` or `>`) can be enforced",
+                        version.unwrap_or_default(),
+                    )));
+                }
+            }
+            if spec.explicit {
+                plan.install.push(name.clone());
+            } else {
+                plan.install_deps.push(name.clone());
+            }
+        }
+        plan.extra = explicit
+            .into_iter()
+            .filter(|name| !wanted.contains(name.as_str()))
+            .filter(|name| !wanted.contains(bare_name(name)))
+            .sorted_unstable()
+            .collect();
+        Ok(plan)
+    }
+}
+
+/// Strips the repository from a qualified package name, e.g.
+/// `homebrew/cask/firefox` gives `firefox`.
+fn bare_name(name: &str) -> &str {
+    name.rsplit('/').next().unwrap_or(name)
+}
+
+/// Returns the [`Dependency`] on the package called `name` with the given
+/// version constraint, where a bare version means an exact one.
+fn dependency(name: &str, version: Option<&str>) -> Result<Dependency> {
+    let name = bare_name(name);
+    match version {
+        Some(version) if version.starts_with(['<', '>', '=']) => format!("{name}{version}"),
+        Some(version) => format!("{name}={version}"),
+        None => name.into(),
+    }
+    .parse()
+}
+
+/// Quotes a string as a TOML basic string.
+fn quote(s: &str) -> String {
+    // A JSON string is also a valid TOML basic string.
+    serde_json::to_string(s).unwrap_or_default()
+}
+
+/// Quotes a TOML key if it cannot be written as a bare one.
+fn quote_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        key.into()
+    } else {
+        quote(key)
+    }
+}
+
+impl fmt::Display for Manifest {
+    /// Formats this manifest in TOML, as read by [`Manifest::load`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(pm) = &self.pm {
+            writeln!(f, "pm = {}\n", quote(pm))?;
+        }
+        writeln!(f, "[packages]")?;
+        for (name, spec) in &self.packages {
+            writeln!(f, "{} = {spec}", quote_key(name))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Spec {
+    /// Formats this spec as a TOML value, i.e. a bare version constraint if
+    /// possible, or an inline table otherwise.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(version) = self
+            .version
+            .as_ref()
+            .filter(|_| self.explicit && self.names.is_empty())
+        {
+            return write!(f, "{}", quote(version));
+        }
+        let mut fields = vec![];
+        if let Some(version) = &self.version {
+            fields.push(format!("version = {}", quote(version)));
+        }
+        if !self.explicit {
+            fields.push("explicit = false".into());
+        }
+        if !self.names.is_empty() {
+            let names = self
+                .names
+                .iter()
+                .sorted_unstable()
+                .map(|(pm, name)| format!("{} = {}", quote_key(pm), quote(name)))
+                .join(", ");
+            fields.push(format!("names = {{ {names} }}"));
+        }
+        if fields.is_empty() {
+            write!(f, "{{}}")
+        } else {
+            write!(f, "{{ {} }}", fields.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let path = std::env::temp_dir().join(format!("pacaptr-test-{}.toml", std::process::id()));
+        let round_trip = |manifest: &Manifest| {
+            std::fs::write(&path, manifest.to_string()).unwrap();
+            let loaded = Manifest::load(&path).unwrap();
+            _ = std::fs::remove_file(&path);
+            loaded
+        };
+
+        let brew = Manifest::from_packages(
+            "brew",
+            [
+                Package::new("docker").version("27.0"),
+                Package::new("homebrew/cask/docker").version("4.30"),
+                Package::new("homebrew/cask/firefox").explicit(false),
+            ],
+        );
+        assert_eq!(
+            brew.packages.keys().collect_vec(),
+            ["docker", "firefox", "homebrew/cask/docker"]
+        );
+        assert_eq!(
+            brew.packages["firefox"].names["brew"],
+            "homebrew/cask/firefox"
+        );
+        assert!(brew.packages["homebrew/cask/docker"].names.is_empty());
+        assert_eq!(round_trip(&brew), brew);
+
+        let emerge =
+            Manifest::from_packages("emerge", [Package::new("app-shells/fish").version("3.7.1")]);
+        assert_eq!(emerge.packages.keys().collect_vec(), ["app-shells/fish"]);
+        assert_eq!(
+            emerge.packages["app-shells/fish"].version.as_deref(),
+            Some(">=3.7.1")
+        );
+        assert_eq!(round_trip(&emerge), emerge);
+    }
+}
@@ -0,0 +1,136 @@
+//! APIs for translating package names across package managers, e.g. `fd` is
+//! called `fd-find` with `apt`.
+//!
+//! The translations are indexed by the `pacman` names of the packages. The
+//! bundled table can be extended and overridden with the `names` config item,
+//! e.g.
+//!
+//! ```toml
+//! [names.fd]
+//! apt = "fd-find"
+//! ```
+
+use std::collections::BTreeMap;
+
+/// The names of the packages with each package manager, indexed by their
+/// `pacman` names.
+pub type Names = BTreeMap<String, BTreeMap<String, String>>;
+
+/// The system package managers, for which the `pacman` names are used when
+/// they are missing from an entry.
+///
+/// The other package managers, e.g. `pip`, have namespaces of their own, so the
+/// names are only translated for them if they are given in an entry.
+const SYSTEM_PMS: &[&str] = &[
+    "apk", "apt", "brew", "choco", "dnf", "emerge", "pacman", "paru", "pkcon", "port", "scoop",
+    "winget", "xbps", "yay", "zypper",
+];
+
+/// The bundled translations, where `pacman` names are used for the system
+/// package managers missing from an entry.
+static BUNDLED: &[(&str, &[(&str, &str)])] = &[
+    (
+        "base-devel",
+        &[("apk", "build-base"), ("apt", "build-essential")],
+    ),
+    (
+        "bind",
+        &[
+            ("apk", "bind-tools"),
+            ("apt", "bind9-dnsutils"),
+            ("dnf", "bind-utils"),
+            ("zypper", "bind-utils"),
+        ],
+    ),
+    ("docker", &[("apt", "docker.io")]),
+    ("fd", &[("apt", "fd-find"), ("dnf", "fd-find")]),
+    ("gnupg", &[("dnf", "gnupg2"), ("zypper", "gpg2")]),
+    ("go", &[("apt", "golang"), ("dnf", "golang")]),
+    (
+        "imagemagick",
+        &[("dnf", "ImageMagick"), ("zypper", "ImageMagick")],
+    ),
+    ("nodejs", &[("brew", "node")]),
+    (
+        "python",
+        &[
+            ("apk", "python3"),
+            ("apt", "python3"),
+            ("dnf", "python3"),
+            ("xbps", "python3"),
+            ("zypper", "python3"),
+        ],
+    ),
+    (
+        "python-pip",
+        &[
+            ("apk", "py3-pip"),
+            ("apt", "python3-pip"),
+            ("dnf", "python3-pip"),
+            ("xbps", "python3-pip"),
+            ("zypper", "python3-pip"),
+        ],
+    ),
+    ("sqlite", &[("apt", "sqlite3")]),
+    ("the_silver_searcher", &[("apt", "silversearcher-ag")]),
+    ("xz", &[("apt", "xz-utils")]),
+];
+
+/// A table of package names across package managers.
+#[must_use]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Table(Names);
+
+impl Table {
+    /// Makes the bundled table, extended and overridden by `extra`.
+    pub fn new(extra: &Names) -> Self {
+        let mut names: Names = BUNDLED
+            .iter()
+            .map(|&(name, entry)| {
+                let entry = entry
+                    .iter()
+                    .map(|&(pm, name)| (pm.into(), name.into()))
+                    .collect();
+                (name.into(), entry)
+            })
+            .collect();
+        for (name, entry) in extra {
+            names
+                .entry(name.clone())
+                .or_default()
+                .extend(entry.iter().map(|(pm, name)| (pm.clone(), name.clone())));
+        }
+        Self(names)
+    }
+
+    /// Translates the package `name` for the package manager `pm`.
+    ///
+    /// `name` is looked up as a `pacman` name first, and then as the name with
+    /// any other package manager. It is returned untouched if it is not found,
+    /// or if the entry found has no name for `pm` and `pm` is not a system
+    /// package manager.
+    #[must_use]
+    pub fn translate<'a>(&'a self, pm: &str, name: &'a str) -> &'a str {
+        let found = self.0.get_key_value(name).or_else(|| {
+            self.0
+                .iter()
+                .find(|(_, entry)| entry.values().any(|n| n == name))
+        });
+        let Some((pacman_name, entry)) = found else {
+            return name;
+        };
+        match entry.get(pm) {
+            Some(name) => name,
+            None if SYSTEM_PMS.contains(&pm) => pacman_name,
+            None => name,
+        }
+    }
+}
+
+/// Returns `true` if the keywords of the given `pacman` operation (e.g. `Rs`)
+/// are package names to be translated, i.e. for `S`, `Si` and `R`.
+#[must_use]
+pub fn translates(op: &str) -> bool {
+    let op = op.to_lowercase();
+    matches!(op.as_str(), "s" | "si" | "sii" | "sw" | "sy") || op.starts_with('r')
+}
@@ -0,0 +1,412 @@
+//! Typed records of packages, as parsed from the output of the underlying
+//! package managers.
+
+use std::{cmp::Ordering, fmt::Write, str::FromStr, sync::LazyLock};
+
+use regex::{Regex, RegexSet};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// A package known to a [`Pm`](crate::pm::Pm), either installed locally or
+/// available from a sync source.
+#[must_use]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Package {
+    /// The name of the package.
+    pub name: String,
+
+    /// The version of the package, either installed or available.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    /// The newer version this package can be upgraded to, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_version: Option<String>,
+
+    /// The repository (or tap, channel, etc.) this package comes from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo: Option<String>,
+
+    /// A short description of the package.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Whether the package is installed locally.
+    #[serde(default)]
+    pub installed: bool,
+
+    /// Whether the package has been installed explicitly (rather than as a
+    /// dependency), if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explicit: Option<bool>,
+
+    /// The installed size of the package in bytes, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+impl Package {
+    /// Makes a new [`Package`] with the given name.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Overrides the value of [`version`](field@Package::version).
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Overrides the value of [`new_version`](field@Package::new_version).
+    pub fn new_version(mut self, new_version: impl Into<String>) -> Self {
+        self.new_version = Some(new_version.into());
+        self
+    }
+
+    /// Overrides the value of [`repo`](field@Package::repo).
+    pub fn repo(mut self, repo: impl Into<String>) -> Self {
+        self.repo = Some(repo.into());
+        self
+    }
+
+    /// Overrides the value of [`description`](field@Package::description).
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Overrides the value of [`installed`](field@Package::installed).
+    pub const fn installed(mut self, installed: bool) -> Self {
+        self.installed = installed;
+        self
+    }
+
+    /// Overrides the value of [`explicit`](field@Package::explicit).
+    pub const fn explicit(mut self, explicit: bool) -> Self {
+        self.explicit = Some(explicit);
+        self
+    }
+
+    /// Overrides the value of [`size`](field@Package::size).
+    pub const fn size(mut self, size: u64) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    /// Checks if the name or the description of this package matches all the
+    /// given `patterns`.
+    #[must_use]
+    pub fn matches(&self, patterns: &RegexSet) -> bool {
+        let haystack = format!(
+            "{} {}",
+            self.name,
+            self.description.as_deref().unwrap_or_default()
+        );
+        patterns.matches(&haystack).into_iter().count() == patterns.len()
+    }
+
+    /// Parses a line in the style of `pacman -Q`, e.g. `bash 5.2-1`, into an
+    /// installed package.
+    #[must_use]
+    pub fn from_q_line(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let pkg = Self::new(words.next()?).installed(true);
+        Some(match words.next() {
+            Some(version) => pkg.version(version),
+            None => pkg,
+        })
+    }
+
+    /// Formats this package in the style of `pacman -Q`, e.g. `bash 5.2-1`.
+    #[must_use]
+    pub fn to_q_line(&self) -> String {
+        self.version.as_ref().map_or_else(
+            || self.name.clone(),
+            |version| format!("{} {version}", self.name),
+        )
+    }
+
+    /// Formats this package in the style of `pacman -Qu`, e.g.
+    /// `bash 5.2-1 -> 5.2-2`.
+    #[must_use]
+    pub fn to_upgrade_line(&self) -> String {
+        let mut line = self.to_q_line();
+        if let Some(new_version) = &self.new_version {
+            _ = write!(line, " -> {new_version}");
+        }
+        line
+    }
+
+    /// Formats this package in the style of `pacman -Qs` and `pacman -Ss`,
+    /// i.e. a `repo/name version` line followed by an indented description.
+    #[must_use]
+    pub fn to_search_entry(&self) -> String {
+        let repo = self
+            .repo
+            .as_deref()
+            .unwrap_or(if self.installed { "local" } else { "sync" });
+        let mut entry = format!("{repo}/{}", self.to_q_line());
+        if self.installed && self.repo.is_some() {
+            entry.push_str(" [installed]");
+        }
+        if let Some(desc) = &self.description {
+            _ = write!(entry, "\n    {desc}");
+        }
+        entry
+    }
+
+    /// Formats this package in the style of `pacman -Si`, i.e. one
+    /// `Field : value` line for each known field.
+    #[must_use]
+    pub fn to_info_block(&self) -> String {
+        let size = self.size.map(format_size);
+        let reason = self.explicit.map(|explicit| {
+            if explicit {
+                "Explicitly installed"
+            } else {
+                "Installed as a dependency for another package"
+            }
+        });
+        [
+            ("Repository", self.repo.as_deref()),
+            ("Name", Some(self.name.as_str())),
+            ("Version", self.version.as_deref()),
+            ("New Version", self.new_version.as_deref()),
+            ("Description", self.description.as_deref()),
+            ("Installed Size", size.as_deref()),
+            ("Install Reason", reason),
+        ]
+        .into_iter()
+        .filter_map(|(field, val)| Some(format!("{field:<16}: {}\n", val?)))
+        .collect()
+    }
+}
+
+/// Formats a size in bytes in the style of `pacman`, e.g. `1.50 MiB`.
+#[allow(clippy::cast_precision_loss)]
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1024. && unit < UNITS.len() - 1 {
+        size /= 1024.;
+        unit += 1;
+    }
+    format!("{size:.2} {}", UNITS[unit])
+}
+
+/// A dependency as accepted by `pacman -T`, e.g. `foo` or `foo>=1.2`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dependency {
+    /// The name of the required package.
+    pub name: String,
+
+    /// The version constraint on the required package, if any.
+    pub constraint: Option<(Ordering, bool, String)>,
+}
+
+impl FromStr for Dependency {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some(idx) = s.find(['<', '>', '=']) else {
+            return Ok(Self {
+                name: s.into(),
+                constraint: None,
+            });
+        };
+        let (name, rest) = s.split_at(idx);
+        // `==` is accepted as well, as in `pip`'s requirement specifiers.
+        let (ord, or_equal, version) = [
+            (">=", Ordering::Greater, true),
+            ("<=", Ordering::Less, true),
+            ("==", Ordering::Equal, true),
+            (">", Ordering::Greater, false),
+            ("<", Ordering::Less, false),
+            ("=", Ordering::Equal, true),
+        ]
+        .into_iter()
+        .find_map(|(op, ord, or_equal)| Some((ord, or_equal, rest.strip_prefix(op)?)))
+        .filter(|(_, _, version)| {
+            !(name.is_empty() || version.is_empty() || version.contains(['<', '>', '=']))
+        })
+        .ok_or_else(|| Error::ArgParseError {
+            msg: format!("ill-formed dependency `{s}`"),
+        })?;
+        Ok(Self {
+            name: name.into(),
+            constraint: Some((ord, or_equal, version.into())),
+        })
+    }
+}
+
+impl Dependency {
+    /// Checks if this dependency is satisfied by the given installed version
+    /// of the package, where `None` means that the package is not installed.
+    #[must_use]
+    pub fn is_satisfied_by(&self, installed: Option<&str>) -> bool {
+        let Some(installed) = installed else {
+            return false;
+        };
+        self.constraint
+            .as_ref()
+            .is_none_or(|(ord, or_equal, version)| {
+                let res = vercmp(installed, version);
+                res == *ord || (*or_equal && res == Ordering::Equal)
+            })
+    }
+}
+
+/// Compares two versions in the `[epoch:]version[-release]` format in the
+/// style of `pacman`'s `vercmp`.
+///
+/// The releases are compared only if both versions have one, so that `1.2-3`
+/// satisfies `=1.2`.
+#[must_use]
+pub fn vercmp(a: &str, b: &str) -> Ordering {
+    fn parse_evr(evr: &str) -> (&str, &str, Option<&str>) {
+        let (epoch, vr) = match evr.split_once(':') {
+            Some((epoch, vr)) if epoch.chars().all(|c| c.is_ascii_digit()) => (epoch, vr),
+            _ => ("0", evr),
+        };
+        let (version, release) = vr
+            .rsplit_once('-')
+            .map_or((vr, None), |(version, release)| (version, Some(release)));
+        (epoch, version, release)
+    }
+
+    let ((ea, va, ra), (eb, vb, rb)) = (parse_evr(a), parse_evr(b));
+    rpmvercmp(ea, eb)
+        .then_with(|| rpmvercmp(va, vb))
+        .then_with(|| match (ra, rb) {
+            (Some(ra), Some(rb)) => rpmvercmp(ra, rb),
+            _ => Ordering::Equal,
+        })
+}
+
+/// Compares two version segments in the style of `rpmvercmp`.
+fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    /// Splits a version into runs of either digits or letters.
+    fn segments(s: &str) -> impl Iterator<Item = &str> {
+        static SEGMENT: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new("[0-9]+|[A-Za-z]+").expect("ill-formed regex for version segments")
+        });
+        SEGMENT.find_iter(s).map(|m| m.as_str())
+    }
+
+    let is_num = |seg: &str| seg.starts_with(|c: char| c.is_ascii_digit());
+    let (mut xs, mut ys) = (segments(a), segments(b));
+    loop {
+        let res = match (xs.next(), ys.next()) {
+            (None, None) => return Ordering::Equal,
+            // A trailing alphabetic segment means a pre-release, e.g. `1.0a < 1.0`.
+            (Some(x), None) => {
+                return if is_num(x) {
+                    Ordering::Greater
+                } else {
+                    Ordering::Less
+                };
+            }
+            (None, Some(y)) => {
+                return if is_num(y) {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                };
+            }
+            (Some(x), Some(y)) => match (is_num(x), is_num(y)) {
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (true, true) => {
+                    let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                    x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+                }
+                (false, false) => x.cmp(y),
+            },
+        };
+        if res.is_ne() {
+            return res;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering::{Equal, Greater, Less};
+
+    use super::*;
+
+    #[test]
+    fn versions() {
+        for (a, b, ord) in [
+            ("1.0", "1.0", Equal),
+            ("1.0", "1.0.1", Less),
+            ("1.10", "1.9", Greater),
+            ("001.2", "1.2", Equal),
+            // Epochs take precedence over the versions.
+            ("1:1.0", "2.0", Greater),
+            ("0:1.0", "1.0", Equal),
+            ("2:0.1", "1:9.9", Greater),
+            // Releases are compared only if both versions have one.
+            ("1.0-1", "1.0-2", Less),
+            ("1.0-2", "1.0", Equal),
+            ("2.36-9+deb12u4", "2.36-9+deb12u3", Greater),
+            // Pre-releases, with or without `~`.
+            ("1.0~rc1", "1.0", Less),
+            ("1.0~rc1", "1.0~rc2", Less),
+            ("1.0~rc1", "0.9", Greater),
+            ("1.0a", "1.0", Less),
+            ("1.0alpha", "1.0beta", Less),
+            ("1.0rc1", "1.0.1", Less),
+        ] {
+            assert_eq!(vercmp(a, b), ord, "vercmp({a:?}, {b:?})");
+            assert_eq!(vercmp(b, a), ord.reverse(), "vercmp({b:?}, {a:?})");
+        }
+    }
+
+    #[test]
+    fn dependencies() {
+        for (dep, name, constraint) in [
+            ("curl", "curl", None),
+            ("curl>=8", "curl", Some((Greater, true, "8"))),
+            ("curl<=8", "curl", Some((Less, true, "8"))),
+            ("curl>8", "curl", Some((Greater, false, "8"))),
+            ("curl<8", "curl", Some((Less, false, "8"))),
+            ("curl=8.1-1", "curl", Some((Equal, true, "8.1-1"))),
+            ("requests==2.31", "requests", Some((Equal, true, "2.31"))),
+        ] {
+            let parsed: Dependency = dep.parse().unwrap();
+            assert_eq!(parsed.name, name, "{dep:?}");
+            assert_eq!(
+                parsed.constraint,
+                constraint.map(|(ord, or_equal, ver)| (ord, or_equal, ver.into())),
+                "{dep:?}"
+            );
+        }
+        for dep in [">=8", "curl>=", "curl=>8", "curl<>8"] {
+            assert!(dep.parse::<Dependency>().is_err(), "{dep:?}");
+        }
+
+        for (dep, installed, satisfied) in [
+            ("curl", Some("8.0"), true),
+            ("curl", None, false),
+            ("curl>=8", Some("8.0-1"), true),
+            ("curl>=8", Some("7.88"), false),
+            ("curl>8", Some("8"), false),
+            ("curl<8", Some("8~rc1"), true),
+            ("curl=8.1", Some("8.1-3"), true),
+            ("curl=8.1-2", Some("8.1-3"), false),
+        ] {
+            let parsed: Dependency = dep.parse().unwrap();
+            assert_eq!(
+                parsed.is_satisfied_by(installed),
+                satisfied,
+                "{dep:?} by {installed:?}"
+            );
+        }
+    }
+}
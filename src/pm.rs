@@ -10,8 +10,10 @@ mod apt;
 mod brew;
 mod choco;
 mod conda;
+mod config_pm;
 mod dnf;
 mod emerge;
+mod pacman;
 mod pip;
 mod pkcon;
 mod port;
@@ -22,23 +24,30 @@ mod winget;
 mod xbps;
 mod zypper;
 
-use std::env;
+use std::{env, future::Future, time::Duration};
 
 use async_trait::async_trait;
 use itertools::Itertools;
 use macro_rules_attribute::macro_rules_attribute;
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+use tokio::time;
 use tt_call::tt_call;
 
+pub use self::config_pm::{MethodDef, PmDef};
 use self::{
-    apk::Apk, apt::Apt, brew::Brew, choco::Choco, conda::Conda, dnf::Dnf, emerge::Emerge, pip::Pip,
-    pkcon::Pkcon, port::Port, scoop::Scoop, tlmgr::Tlmgr, unknown::Unknown, winget::Winget,
-    xbps::Xbps, zypper::Zypper,
+    apk::Apk, apt::Apt, brew::Brew, choco::Choco, conda::Conda, config_pm::ConfigPm, dnf::Dnf,
+    emerge::Emerge, pacman::Pacman, pip::Pip, pkcon::Pkcon, port::Port, scoop::Scoop, tlmgr::Tlmgr,
+    unknown::Unknown, winget::Winget, xbps::Xbps, zypper::Zypper,
 };
 use crate::{
     config::Config,
-    error::Result,
+    error::{Error, Failure, Result},
     exec::{self, Cmd, Mode, Output, is_exe},
-    print::{println_quoted, prompt},
+    history,
+    lock::{self, Lock},
+    package::{Dependency, Package},
+    print::{println, println_quoted, prompt},
 };
 
 /// The list of [`pacman`](https://wiki.archlinux.org/index.php/Pacman) methods supported by [`pacaptr`](crate).
@@ -49,15 +58,42 @@ macro_rules! methods {
         tt_call::tt_return! {
             $caller
             methods = [{
+                /// Dd marks packages as non-explicitly installed, aka `--asdeps`.
+                async fn dd;
+
+                /// De marks packages as explicitly installed, aka `--asexplicit`.
+                async fn de;
+
+                /// F queries the packages from the sync sources which provide FILE.
+                async fn f;
+
+                /// Fl displays files provided by remote package.
+                async fn fl;
+
+                /// Fx queries the packages from the sync sources which provide a file matching the regex.
+                async fn fx;
+
+                /// Fy refreshes the local files database.
+                async fn fy;
+
                 /// Q generates a list of installed packages.
                 async fn q;
 
                 /// Qc shows the changelog of a package.
                 async fn qc;
 
+                /// Qd lists packages installed as dependencies (not explicitly).
+                async fn qd;
+
+                /// Qdt lists orphans, i.e. packages installed as dependencies but no longer required by any installed package.
+                async fn qdt;
+
                 /// Qe lists packages installed explicitly (not as dependencies).
                 async fn qe;
 
+                /// Qh lists packages held back from upgrades, aka `Q --held`.
+                async fn qh;
+
                 /// Qi displays local package information: name, version, description, etc.
                 async fn qi;
 
@@ -82,6 +118,9 @@ macro_rules! methods {
                 /// Qs searches locally installed package for names or descriptions.
                 async fn qs;
 
+                /// Qt lists packages not required by any installed package.
+                async fn qt;
+
                 /// Qu lists packages which have an update available.
                 async fn qu;
 
@@ -105,6 +144,12 @@ macro_rules! methods {
                 /// S installs one or more packages by name.
                 async fn s;
 
+                /// Sas searches for package(s) in the AUR only, aka `Ssa`.
+                async fn sas;
+
+                /// Sau updates outdated packages from the AUR, aka `Sua`.
+                async fn sau;
+
                 /// Sc removes all the cached packages that are not currently installed, and the unused sync database.
                 async fn sc;
 
@@ -141,6 +186,9 @@ macro_rules! methods {
                 /// Sy refreshes the local package database.
                 async fn sy;
 
+                /// T prints the dependencies which are not satisfied by the installed packages.
+                async fn t;
+
                 /// U upgrades or adds package(s) to the system and installs the required dependencies from sync repositories.
                 async fn u;
             }]
@@ -221,6 +269,83 @@ pub trait Pm: Sync {
     {
         Box::new(self)
     }
+
+    /// Returns the locks taken by this package manager on its database, to be
+    /// waited for with `--wait-lock`.
+    fn locks(&self) -> &'static [Lock] {
+        &[]
+    }
+
+    /// Returns the case-insensitive regex patterns recognizing the failures
+    /// specific to this package manager in its output, tried before
+    /// [`Failure::GENERIC`].
+    fn failures(&self) -> &'static [(Failure, &'static str)] {
+        &[]
+    }
+
+    /// Returns `true` if this package manager reduces its listings to bare
+    /// package names by itself when [`Config::names_only`] is set, e.g. with
+    /// `pacman -q`.
+    fn native_names_only(&self) -> bool {
+        false
+    }
+
+    /// Lists the installed packages as [`Package`] records, aka `Q`.
+    async fn list_installed(&self, _kws: &[&str], _flags: &[&str]) -> Result<Vec<Package>> {
+        make_op_body!(self, list_installed)
+    }
+
+    /// Gets the [`Package`] records of the given installed packages, aka `Qi`.
+    async fn info_installed(&self, _kws: &[&str], _flags: &[&str]) -> Result<Vec<Package>> {
+        make_op_body!(self, info_installed)
+    }
+
+    /// Lists the explicitly installed packages as [`Package`] records, aka
+    /// `Qe`.
+    ///
+    /// Defaults to filtering the records of [`Pm::info_installed`].
+    async fn list_explicit(&self, flags: &[&str]) -> Result<Vec<Package>> {
+        match self.info_installed(&[], flags).await {
+            Ok(pkgs) => Ok(pkgs
+                .into_iter()
+                .filter(|pkg| pkg.explicit == Some(true))
+                .collect()),
+            Err(Error::OperationUnimplementedError { .. }) => {
+                make_op_body!(self, list_explicit)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Lists the packages which have an update available as [`Package`]
+    /// records, aka `Qu`.
+    async fn list_upgrades(&self, _kws: &[&str], _flags: &[&str]) -> Result<Vec<Package>> {
+        make_op_body!(self, list_upgrades)
+    }
+
+    /// Searches the sync sources for packages matching all the given
+    /// keywords, aka `Ss`.
+    async fn search(&self, _kws: &[&str], _flags: &[&str]) -> Result<Vec<Package>> {
+        make_op_body!(self, search)
+    }
+
+    /// Gets the [`Package`] records of the given packages from the sync
+    /// sources, aka `Si`.
+    async fn info(&self, _kws: &[&str], _flags: &[&str]) -> Result<Vec<Package>> {
+        make_op_body!(self, info)
+    }
+
+    /// Gets the installed version of the given package, or `None` if it is not
+    /// installed, as used by `T`.
+    async fn installed_version(&self, _name: &str, _flags: &[&str]) -> Result<Option<String>> {
+        make_op_body!(self, installed_version)
+    }
+
+    /// Reverts the last `n` transactions with the package manager's own
+    /// history, as used by `--undo`.
+    async fn undo(&self, _n: usize, _flags: &[&str]) -> Result<()> {
+        make_op_body!(self, undo)
+    }
 }
 
 /// An owned, dynamically typed [`Pm`].
@@ -234,6 +359,12 @@ impl From<Config> for BoxPm<'_> {
         // we should fall back to automatic detection and overwrite `cfg`.
         let pm = cfg.default_pm.get_or_insert_with(|| detect_pm_str().into());
 
+        // The package managers defined in the config take precedence.
+        if let Some(def) = cfg.pms.get(pm.as_str()) {
+            let (name, def) = (pm.clone(), def.clone());
+            return ConfigPm::new(name, def, cfg).boxed();
+        }
+
         #[allow(clippy::match_single_binding)]
         match pm.as_ref() {
             // Chocolatey
@@ -263,6 +394,9 @@ impl From<Config> for BoxPm<'_> {
             // Portage for Gentoo
             "emerge" => Emerge::new(cfg).boxed(),
 
+            // Pacman for Arch Linux
+            "pacman" => Pacman::new(cfg).boxed(),
+
             // Xbps for Void Linux
             "xbps" | "xbps-install" => Xbps::new(cfg).boxed(),
 
@@ -277,6 +411,9 @@ impl From<Config> for BoxPm<'_> {
             // Pip
             "pip" | "pip3" => Pip::new(cfg).boxed(),
 
+            // AUR helpers
+            "paru" | "yay" => Pacman::new(cfg).boxed(),
+
             // PackageKit
             "pkcon" => Pkcon::new(cfg).boxed(),
 
@@ -296,20 +433,10 @@ impl From<Config> for BoxPm<'_> {
     }
 }
 
-/// Detects the name of the package manager to be used in auto dispatch.
-#[must_use]
-fn detect_pm_str() -> &'static str {
-    /// Check if one of the following conditions are met:
-    /// - `$TERMUX_APP_PACKAGE_MANAGER` is `apt`;
-    /// - `$TERMUX_MAIN_PACKAGE_FORMAT` is `debian`.
-    ///
-    /// See: <https://github.com/rami3l/pacaptr/issues/576#issuecomment-1565122604>
-    fn is_termux_apt() -> bool {
-        env::var("TERMUX_APP_PACKAGE_MANAGER").as_deref() == Ok("apt")
-            || env::var("TERMUX_MAIN_PACKAGE_FORMAT").as_deref() == Ok("debian")
-    }
-
-    let pairs: &[(&str, &str)] = match () {
+/// Returns the package managers to look for on the current platform, as pairs
+/// of names and usual paths, in the order of preference.
+const fn pm_candidates() -> &'static [(&'static str, &'static str)] {
+    match () {
         () if cfg!(windows) => &[("scoop", ""), ("choco", ""), ("winget", "")],
 
         () if cfg!(target_os = "macos") => &[
@@ -325,22 +452,89 @@ fn detect_pm_str() -> &'static str {
             ("apt", "/usr/bin/apt"),
             ("dnf", "/usr/bin/dnf"),
             ("emerge", "/usr/bin/emerge"),
+            ("pacman", "/usr/bin/pacman"),
             ("xbps-install", "/usr/bin/xbps-install"),
             ("zypper", "/usr/bin/zypper"),
         ],
 
         () => &[],
-    };
+    }
+}
+
+/// Returns the name under which the detected package manager `name` should be
+/// dispatched, i.e. `pkg` for `apt` on Termux, or `name` itself otherwise.
+fn detected_pm_str(name: &'static str) -> &'static str {
+    /// Check if one of the following conditions are met:
+    /// - `$TERMUX_APP_PACKAGE_MANAGER` is `apt`;
+    /// - `$TERMUX_MAIN_PACKAGE_FORMAT` is `debian`.
+    ///
+    /// See: <https://github.com/rami3l/pacaptr/issues/576#issuecomment-1565122604>
+    fn is_termux_apt() -> bool {
+        env::var("TERMUX_APP_PACKAGE_MANAGER").as_deref() == Ok("apt")
+            || env::var("TERMUX_MAIN_PACKAGE_FORMAT").as_deref() == Ok("debian")
+    }
+
+    if name == "apt" && is_termux_apt() {
+        return "pkg";
+    }
+    name
+}
 
-    pairs
+/// Detects the name of the package manager to be used in auto dispatch.
+#[must_use]
+fn detect_pm_str() -> &'static str {
+    pm_candidates()
         .iter()
         .find_map(|&(name, path)| is_exe(name, path).then_some(name))
-        .map_or("unknown", |name| {
-            if name == "apt" && is_termux_apt() {
-                return "pkg";
-            }
-            name
-        })
+        .map_or("unknown", detected_pm_str)
+}
+
+/// Detects the names of all the package managers available, as used by
+/// `--using all`.
+///
+/// Apart from the system package managers, this also includes the
+/// cross-platform ones, e.g. `brew` on Linux, `conda` and `pip`.
+#[must_use]
+pub fn detect_pm_strs() -> Vec<&'static str> {
+    /// The package managers which might be found on any platform.
+    const CROSS_PLATFORM: &[(&str, &str)] =
+        &[("brew", ""), ("conda", ""), ("pip3", ""), ("tlmgr", "")];
+
+    pm_candidates()
+        .iter()
+        .chain(CROSS_PLATFORM)
+        .filter(|&&(name, path)| is_exe(name, path))
+        .map(|&(name, _)| detected_pm_str(name))
+        .unique()
+        .collect()
+}
+
+tokio::task_local! {
+    /// The `pacman` operation (e.g. `Sc`) of the cleanup performed after a
+    /// command with `--no-cache`, to be recorded in the history log instead of
+    /// [`Config::op`].
+    static CLEANUP_OP: &'static str;
+}
+
+/// Returns `true` if the `pacman` operation `op` (e.g. `Suy`) fetches packages
+/// from the network, i.e. `-S`, `-Sy`, `-Su` or `-Sw` and their combinations.
+fn is_sync(op: &str) -> bool {
+    op.strip_prefix(['S', 's']).is_some_and(|flags| {
+        flags
+            .chars()
+            .all(|c| "uwy".contains(c.to_ascii_lowercase()))
+    })
+}
+
+/// Returns `true` if the `pacman` operation `op` (e.g. `Qe`) lists or searches
+/// packages, i.e. `-Q`, `-Qs`, `-Ss`, `-Sl` and the like, whose output `-q`
+/// reduces to bare package names.
+fn is_listing(op: &str) -> bool {
+    match op.split_at_checked(1) {
+        Some(("Q", flags)) => flags.chars().all(|c| "demstu".contains(c)),
+        Some(("S", flags)) => !flags.is_empty() && flags.chars().all(|c| "agls".contains(c)),
+        _ => false,
+    }
 }
 
 /// Extra implementation helper functions for [`Pm`],
@@ -379,26 +573,84 @@ pub trait PmHelper: Pm {
         }
 
         let cfg = self.cfg();
+        cmd = cmd.elevate(cfg.elevate());
+
+        // Let the other processes finish with the package database first.
+        if let (Some(secs), Some(op)) = (cfg.wait_lock, &cfg.op)
+            && history::is_mutating(op)
+            && !cfg.dry_run
+            && !matches!(mode, PmMode::Mute)
+        {
+            lock::wait(self.locks(), Duration::from_secs(secs), op).await?;
+        }
+
+        // Note the packages already installed, which `--undo` should not remove.
+        let op = CLEANUP_OP.try_with(|&op| op).ok().or(cfg.op.as_deref());
+        let installed = if op.is_some_and(history::Entry::is_install)
+            && !cfg.dry_run
+            && !matches!(mode, PmMode::Mute)
+        {
+            self.installed_kws(&cmd.kws).await
+        } else {
+            Some(vec![])
+        };
 
         // `--dry-run` should apply to both the main command and the cleanup.
         let res = match &strat.dry_run {
-            DryRunStrategy::PrintCmd if cfg.dry_run => cmd.clone().exec(Mode::PrintCmd).await?,
+            DryRunStrategy::PrintCmd if cfg.dry_run => cmd.clone().exec(Mode::PrintCmd).await,
             DryRunStrategy::WithFlags(v) if cfg.dry_run => {
                 cmd.flags.extend(v.clone());
                 // -- A dry run with extra flags does not need `sudo`. --
                 cmd = cmd.sudo(false);
-                run(cfg, &cmd, mode, strat).await?
+                run(cfg, &cmd, mode, strat).await
+            }
+            // `-q` reduces the native listing to bare package names.
+            _ if cfg.names_only
+                && !self.native_names_only()
+                && cfg.op.as_deref().is_some_and(is_listing)
+                && !matches!(mode, PmMode::Mute) =>
+            {
+                if !cfg.quiet() {
+                    println_quoted(&*prompt::RUNNING, &cmd);
+                }
+                let out = run(cfg, &cmd, PmMode::Mute, strat).await?;
+                exec::package_names(&String::from_utf8(out.clone())?)
+                    .for_each(|name| println!("{name}"));
+                Ok(out)
             }
-            _ => run(cfg, &cmd, mode, strat).await?,
+            _ => self.retry_network(|| run(cfg, &cmd, mode, strat)).await,
         };
 
+        // Report the interruptions with the `pacman` operation being performed.
+        let res = res.map_err(|e| match (e, &cfg.op) {
+            (Error::CmdInterruptedError { .. }, Some(op)) => Error::CmdInterruptedError {
+                op: format!("-{op}"),
+            },
+            (e, _) => e,
+        });
+
+        // Record the mutating commands in the history log, leaving out the
+        // queries run silently in the meantime.
+        if let Some(op) = op
+            && history::is_mutating(op)
+            && !matches!(mode, PmMode::Mute)
+        {
+            self.record(op, &cmd, &res, installed);
+        }
+        // The muted commands are internal queries, whose callers might expect
+        // the raw exit codes.
+        let res = res.map_err(|e| match mode {
+            PmMode::Mute => e,
+            _ => self.classify(e),
+        })?;
+
         // Perform the cleanup.
         if cfg.no_cache {
             let flags = cmd.flags.iter().map(AsRef::as_ref).collect_vec();
             match &strat.no_cache {
-                NoCacheStrategy::Sc => self.sc(&[], &flags).await?,
-                NoCacheStrategy::Scc => self.scc(&[], &flags).await?,
-                NoCacheStrategy::Sccc => self.sccc(&[], &flags).await?,
+                NoCacheStrategy::Sc => CLEANUP_OP.scope("Sc", self.sc(&[], &flags)).await?,
+                NoCacheStrategy::Scc => CLEANUP_OP.scope("Scc", self.scc(&[], &flags)).await?,
+                NoCacheStrategy::Sccc => CLEANUP_OP.scope("Sccc", self.sccc(&[], &flags)).await?,
                 _ => (),
             }
         }
@@ -406,10 +658,115 @@ pub trait PmHelper: Pm {
         Ok(res)
     }
 
-    /// Returns the default [`PmMode`] for this [`Pm`].
+    /// Classifies an [`Error::CmdStatusCodeError`] into a more specific error
+    /// by matching its output against [`Pm::failures`] and then
+    /// [`Failure::GENERIC`], leaving it untouched if nothing matches.
+    fn classify(&self, e: Error) -> Error {
+        let Error::CmdStatusCodeError { code, output } = e else {
+            return e;
+        };
+        match self.failure(&output) {
+            Some(failure) => failure.into_error(code, output),
+            None => Error::CmdStatusCodeError { code, output },
+        }
+    }
+
+    /// Returns the [`Failure`] recognized in the `output` of a failed command
+    /// by [`Pm::failures`] or [`Failure::GENERIC`], if any.
+    fn failure(&self, output: &[u8]) -> Option<Failure> {
+        let text = String::from_utf8_lossy(output);
+        self.failures()
+            .iter()
+            .chain(Failure::GENERIC)
+            .find(|(_, pat)| {
+                RegexBuilder::new(pat)
+                    .case_insensitive(true)
+                    .multi_line(true)
+                    .build()
+                    .is_ok_and(|re| re.is_match(&text))
+            })
+            .map(|&(failure, _)| failure)
+    }
+
+    /// Executes a command with [`PmHelper::check_output`]'s `run`, retrying it
+    /// with an exponential backoff as long as it fails with a
+    /// [`Failure::Network`], up to the `retries` config item.
+    ///
+    /// Only one command is retried at a time, so that a failed install is not
+    /// preceded by yet another refresh of the package database, e.g. in `-Suy`.
+    ///
+    /// # Errors
+    /// Returns an [`Error::CmdInterruptedError`] if interrupted while waiting
+    /// for the next attempt.
+    async fn retry_network<'a, F, Fut>(&self, run: F) -> Result<Output>
+    where
+        F: Fn() -> Fut + Send + 'a,
+        Fut: Future<Output = Result<Output>> + Send + 'a,
+    {
+        let cfg = self.cfg();
+        let retries = match &cfg.op {
+            Some(op) if !cfg.dry_run && is_sync(op) => cfg.retries.unwrap_or(0),
+            _ => 0,
+        };
+        let mut interrupts = exec::interrupts();
+        let mut delay = cfg.backoff();
+        let mut res = run().await;
+        for attempt in 1..=retries {
+            match &res {
+                Err(Error::CmdStatusCodeError { output, .. })
+                    if self.failure(output) == Some(Failure::Network) => {}
+                _ => break,
+            }
+            println(
+                &*prompt::INFO,
+                format!(
+                    "network failure, retrying in {}s ({attempt}/{retries})",
+                    delay.as_secs()
+                ),
+            );
+            tokio::select! {
+                () = time::sleep(delay) => (),
+                _ = interrupts.changed() => {
+                    return Err(Error::CmdInterruptedError {
+                        op: cfg.op.as_deref().map(|op| format!("-{op}")).unwrap_or_default(),
+                    });
+                }
+            }
+            delay *= 2;
+            res = run().await;
+        }
+        res
+    }
+
+    /// Executes a command in [`PmMode::Mute`] and returns its output as a
+    /// [`String`], usually for it to be parsed afterwards.
+    async fn check_output_str(&self, cmd: Cmd) -> Result<String> {
+        let out_bytes = self
+            .check_output(cmd, PmMode::Mute, &Strategy::default())
+            .await?;
+        Ok(String::from_utf8(out_bytes)?)
+    }
+
+    /// Executes a command in [`PmMode::Mute`] and returns its output as a
+    /// [`String`], or `None` if the command exits with a non-zero code, usually
+    /// meaning that nothing has been found.
+    async fn try_output_str(&self, cmd: Cmd) -> Result<Option<String>> {
+        match self.check_output_str(cmd).await {
+            Ok(out) => Ok(Some(out)),
+            Err(Error::CmdStatusCodeError { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the default [`PmMode`] for this [`Pm`], i.e. [`PmMode::Pty`]
+    /// if `stdout` is a TTY, or [`PmMode::CheckErr`] otherwise.
     fn default_mode(&self) -> PmMode {
         let quiet = self.cfg().quiet();
-        PmMode::CheckErr { quiet }
+        if console::Term::stdout().is_term() {
+            PmMode::Pty { quiet }
+        } else {
+            PmMode::CheckErr { quiet }
+        }
     }
 
     /// Executes a command in the context of the [`Pm`] implementation,
@@ -418,6 +775,37 @@ pub trait PmHelper: Pm {
         self.check_output(cmd, mode, strat).await.map(|_| ())
     }
 
+    /// Records the command `cmd` performing the `pacman` operation `op` in the
+    /// history log, along with its result and the keywords already installed
+    /// before (see [`history::Entry::installed`]).
+    fn record(&self, op: &str, cmd: &Cmd, res: &Result<Output>, installed: Option<Vec<String>>) {
+        let status = match res {
+            Ok(_) => Some(0),
+            Err(Error::CmdStatusCodeError { code, .. }) => Some(*code),
+            Err(Error::CmdInterruptedError { .. }) => None,
+            // Same as the shells for the commands that cannot be run.
+            Err(_) => Some(127),
+        };
+        let entry = history::Entry::new(self.name(), op, cmd, status, self.cfg().dry_run)
+            .installed(installed);
+        if let Err(e) = history::append(&entry) {
+            println(&*prompt::INFO, format!("failed to write history: {e}"));
+        }
+    }
+
+    /// Returns the keywords naming the packages already installed, as found by
+    /// [`Pm::installed_version`], or `None` if they cannot be told apart, e.g.
+    /// when it is unimplemented.
+    async fn installed_kws(&self, kws: &[String]) -> Option<Vec<String>> {
+        let mut installed = vec![];
+        for kw in kws {
+            if self.installed_version(kw, &[]).await.ok()?.is_some() {
+                installed.push(kw.clone());
+            }
+        }
+        Some(installed)
+    }
+
     /// Executes a command in the context of the [`Pm`] implementation with
     /// default settings.
     async fn run(&self, cmd: Cmd) -> Result<()> {
@@ -451,9 +839,160 @@ pub trait PmHelper: Pm {
             .await?;
         exec::grep_print_with_header(&String::from_utf8(out_bytes)?, patterns, header_lines)
     }
+
+    /// Executes a command listing one package per line, and prints the lines
+    /// of the packages named in `kws`, as found by `name_of`.
+    /// If `kws` is empty, the command is run as is.
+    async fn search_names(
+        &self,
+        cmd: Cmd,
+        kws: &[&str],
+        name_of: for<'l> fn(&'l str) -> Option<&'l str>,
+    ) -> Result<()> {
+        if kws.is_empty() {
+            return self.run(cmd).await;
+        }
+        let cfg = self.cfg();
+        if !(cfg.dry_run || cfg.quiet()) {
+            println_quoted(&*prompt::RUNNING, &cmd);
+        }
+        let out_bytes = self
+            .check_output(cmd, PmMode::Mute, &Strategy::default())
+            .await?;
+        String::from_utf8(out_bytes)?
+            .lines()
+            .filter(|ln| name_of(ln).is_some_and(|name| kws.contains(&name)))
+            .for_each(|ln| println!("{ln}"));
+        Ok(())
+    }
+
+    /// Returns the packages to be held back when upgrading the system, as given
+    /// by `--ignore` and the `ignore` config item.
+    ///
+    /// # Errors
+    /// Returns an [`Error::ArgParseError`] if any package group is to be held
+    /// back, which is only supported by `pacman` and its wrappers.
+    fn ignored(&self) -> Result<Vec<&str>> {
+        let cfg = self.cfg();
+        if !cfg.ignore_group.is_empty() {
+            return Err(Error::ArgParseError {
+                msg: format!("`--ignoregroup` is unsupported for `{}`", self.name()),
+            });
+        }
+        Ok(cfg.ignore.iter().map(AsRef::as_ref).collect())
+    }
+
+    /// Runs the given `hold` command with the packages returned by
+    /// [`PmHelper::ignored`] as keywords, if there is any.
+    async fn hold_ignored(&self, hold: Cmd) -> Result<()> {
+        let ignored = self.ignored()?;
+        if ignored.is_empty() {
+            return Ok(());
+        }
+        self.run(hold.kws(ignored)).await
+    }
+
+    /// Checks the dependencies given as `kws` (e.g. `foo>=1.2`) against the
+    /// versions returned by [`Pm::installed_version`], printing the unsatisfied
+    /// ones in the style of `pacman -T`.
+    ///
+    /// # Errors
+    /// Returns an [`Error::UnsatisfiedDepsError`] if any of the dependencies is
+    /// unsatisfied.
+    async fn deptest(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        let mut unsatisfied = 0;
+        for &kw in kws {
+            let dep: Dependency = kw.parse()?;
+            let installed = self.installed_version(&dep.name, flags).await?;
+            if !dep.is_satisfied_by(installed.as_deref()) {
+                println!("{kw}");
+                unsatisfied += 1;
+            }
+        }
+        if unsatisfied > 0 {
+            return Err(Error::UnsatisfiedDepsError(unsatisfied));
+        }
+        Ok(())
+    }
+
+    /// Prints a package formatted with `fmt`, or only its name if
+    /// [`Config::names_only`] is set.
+    fn print_package(&self, pkg: &Package, fmt: impl FnOnce(&Package) -> String) {
+        if self.cfg().names_only {
+            println!("{}", pkg.name);
+        } else {
+            println!("{}", fmt(pkg));
+        }
+    }
+
+    /// Prints the installed packages returned by [`Pm::list_installed`] in the
+    /// style of `pacman -Q`.
+    async fn print_installed(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.list_installed(kws, flags)
+            .await?
+            .iter()
+            .for_each(|pkg| self.print_package(pkg, Package::to_q_line));
+        Ok(())
+    }
+
+    /// Prints the installed packages returned by [`Pm::list_explicit`] in the
+    /// style of `pacman -Qe`.
+    async fn print_explicit(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.list_explicit(flags)
+            .await?
+            .iter()
+            .filter(|pkg| kws.is_empty() || kws.contains(&pkg.name.as_str()))
+            .for_each(|pkg| self.print_package(pkg, Package::to_q_line));
+        Ok(())
+    }
+
+    /// Prints the installed packages returned by [`Pm::info_installed`] which
+    /// are known to have been installed as dependencies, in the style of
+    /// `pacman -Qd`.
+    async fn print_installed_deps(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.info_installed(kws, flags)
+            .await?
+            .iter()
+            .filter(|pkg| pkg.explicit == Some(false))
+            .for_each(|pkg| self.print_package(pkg, Package::to_q_line));
+        Ok(())
+    }
+
+    /// Prints the installed packages returned by [`Pm::list_installed`] whose
+    /// names or descriptions match against the given regex `patterns`, in the
+    /// style of `pacman -Qs`.
+    async fn print_installed_search(&self, patterns: &[&str], flags: &[&str]) -> Result<()> {
+        let patterns = exec::grep_patterns(patterns)?;
+        self.list_installed(&[], flags)
+            .await?
+            .iter()
+            .filter(|pkg| pkg.matches(&patterns))
+            .for_each(|pkg| self.print_package(pkg, Package::to_search_entry));
+        Ok(())
+    }
+
+    /// Prints the packages returned by [`Pm::list_upgrades`] in the style of
+    /// `pacman -Qu`.
+    async fn print_upgrades(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.list_upgrades(kws, flags)
+            .await?
+            .iter()
+            .for_each(|pkg| self.print_package(pkg, Package::to_upgrade_line));
+        Ok(())
+    }
+
+    /// Prints the packages returned by [`Pm::search`] in the style of
+    /// `pacman -Ss`.
+    async fn print_search(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.search(kws, flags)
+            .await?
+            .iter()
+            .for_each(|pkg| self.print_package(pkg, Package::to_search_entry));
+        Ok(())
+    }
 }
 
-impl<P: Pm> PmHelper for P {}
+impl<P: Pm + ?Sized> PmHelper for P {}
 
 /// Different ways in which a command shall be dealt with.
 ///
@@ -483,6 +1022,15 @@ pub enum PmMode {
         /// Whether the log output should be suppressed.
         quiet: bool,
     },
+
+    /// Prints out the command which should be executed, runs it attached to a
+    /// pseudo-terminal and collects its `stdout`/`stderr` combined.
+    ///
+    /// This will work with a colored `stdout` and with progress bars.
+    Pty {
+        /// Whether the log output should be suppressed.
+        quiet: bool,
+    },
 }
 
 impl From<PmMode> for Mode {
@@ -491,13 +1039,15 @@ impl From<PmMode> for Mode {
             PmMode::Mute => Self::Mute,
             PmMode::CheckAll { quiet } => Self::CheckAll { quiet },
             PmMode::CheckErr { quiet } => Self::CheckErr { quiet },
+            PmMode::Pty { quiet } => Self::Pty { quiet },
         }
     }
 }
 
 /// A set of intrinsic properties of a command in the context of a specific
 /// package manager, indicating how it is run.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 #[must_use]
 pub struct Strategy {
     /// How a dry run is dealt with.
@@ -514,7 +1064,8 @@ pub struct Strategy {
 ///
 /// Default value: [`DryRunStrategy::PrintCmd`].
 #[must_use]
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum DryRunStrategy {
     /// Prints the command to be run, and stop.
     #[default]
@@ -534,7 +1085,8 @@ impl DryRunStrategy {
 ///
 /// Default value: [`PromptStrategy::None`].
 #[must_use]
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PromptStrategy {
     /// There is no prompt.
     #[default]
@@ -567,7 +1119,8 @@ impl PromptStrategy {
 ///
 /// Default value: [`PromptStrategy::None`].
 #[must_use]
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum NoCacheStrategy {
     /// Does not clean cache.
     /// This variant MUST be used when implementing cache cleaning methods like
@@ -630,6 +1183,36 @@ pub mod tests {
                 &self.cfg
             }
 
+            async fn list_installed(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+                make_mock_op_body!(self, kws, flags, list_installed)
+            }
+
+            async fn info_installed(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+                make_mock_op_body!(self, kws, flags, info_installed)
+            }
+
+            async fn list_upgrades(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+                make_mock_op_body!(self, kws, flags, list_upgrades)
+            }
+
+            async fn search(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+                make_mock_op_body!(self, kws, flags, search)
+            }
+
+            async fn info(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+                make_mock_op_body!(self, kws, flags, info)
+            }
+
+            async fn installed_version(&self, name: &str, flags: &[&str]) -> Result<Option<String>> {
+                let kws = &[name];
+                make_mock_op_body!(self, kws, flags, installed_version)
+            }
+
+            // The packages to be compared against a manifest by `sync-manifest`.
+            async fn list_explicit(&self, _flags: &[&str]) -> Result<Vec<Package>> {
+                Ok(["curl", "wget"].map(|name| Package::new(name).explicit(true)).into())
+            }
+
             // * Automatically generated methods below... *
             $( async fn $method(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
                     make_mock_op_body!(self, kws, flags, $method)
@@ -1,13 +1,20 @@
 #![doc = doc_self!()]
 
-use std::sync::LazyLock;
+use std::{collections::HashSet, fs, path::Path, sync::LazyLock};
 
 use async_trait::async_trait;
 use indoc::indoc;
+use itertools::Itertools;
 use tap::prelude::*;
 
 use super::{NoCacheStrategy, Pm, PmHelper, PromptStrategy, Strategy};
-use crate::{config::Config, error::Result, exec::Cmd};
+use crate::{
+    config::Config,
+    error::{Failure, Result},
+    exec::{self, Cmd},
+    lock::Lock,
+    package::Package,
+};
 
 macro_rules! doc_self {
     () => {
@@ -35,12 +42,61 @@ static STRAT_INSTALL: LazyLock<Strategy> = LazyLock::new(|| Strategy {
     ..Strategy::default()
 });
 
+/// The file listing the packages explicitly installed by the user.
+const WORLD_FILE: &str = "/etc/apk/world";
+
+/// Splits a `name-version-rN` string into a [`Package`] record.
+fn parse_pkgver(pkgver: &str) -> Option<Package> {
+    let mut parts = pkgver.rsplitn(3, '-');
+    let (rel, ver, name) = (parts.next()?, parts.next()?, parts.next()?);
+    Some(Package::new(name).version(format!("{ver}-{rel}")))
+}
+
+/// Parses the output of `apk info -vv` or `apk search -v`, which is made of
+/// `name-version-rN - description` lines.
+fn parse_apk_list(out: &str) -> Vec<Package> {
+    out.lines()
+        .filter_map(|ln| {
+            let (pkgver, desc) = ln.split_once(" - ")?;
+            parse_pkgver(pkgver).map(|pkg| pkg.description(desc))
+        })
+        .collect()
+}
+
+/// Parses the output of `apk info -r`, returning the names of the packages
+/// which are required by at least one other installed package.
+fn parse_required_by(out: &str) -> HashSet<String> {
+    out.split("\n\n")
+        .filter_map(|block| {
+            let mut lines = block.lines().filter(|ln| !ln.trim().is_empty());
+            let pkgver = lines.next()?.strip_suffix(" is required by:")?;
+            lines.next()?;
+            parse_pkgver(pkgver).map(|pkg| pkg.name)
+        })
+        .collect()
+}
+
 impl Apk {
     #[must_use]
     #[allow(missing_docs)]
     pub const fn new(cfg: Config) -> Self {
         Self { cfg }
     }
+
+    /// Pins the packages returned by [`PmHelper::ignored`] to their installed
+    /// versions in the world file, so that they are held back from upgrades.
+    async fn pin_ignored(&self) -> Result<()> {
+        let mut pins = vec![];
+        for name in self.ignored()? {
+            if let Some(version) = self.installed_version(name, &[]).await? {
+                pins.push(format!("{name}={version}"));
+            }
+        }
+        if pins.is_empty() {
+            return Ok(());
+        }
+        self.run(Cmd::with_sudo(["apk", "add"]).kws(pins)).await
+    }
 }
 
 #[async_trait]
@@ -54,18 +110,228 @@ impl Pm for Apk {
         &self.cfg
     }
 
+    fn locks(&self) -> &'static [Lock] {
+        &[Lock::File("/lib/apk/db/lock")]
+    }
+
+    fn failures(&self) -> &'static [(Failure, &'static str)] {
+        &[
+            (Failure::LockHeld, r"unable to lock database"),
+            (
+                Failure::PackageNotFound,
+                r"unable to select packages|no such package",
+            ),
+            (
+                Failure::Network,
+                r"temporary error \(try again later\)|network error|dns lookup error",
+            ),
+            (Failure::PackageConflict, r"conflicts:"),
+        ]
+    }
+
+    /// Lists the installed packages as [`Package`] records, aka `Q`.
+    async fn list_installed(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        let out = self
+            .check_output_str(Cmd::new(["apk", "info", "-vv"]).flags(flags))
+            .await?;
+        Ok(parse_apk_list(&out)
+            .into_iter()
+            .filter(|pkg| kws.is_empty() || kws.contains(&pkg.name.as_str()))
+            .map(|pkg| pkg.installed(true))
+            .collect())
+    }
+
+    /// Gets the [`Package`] records of the given installed packages, aka `Qi`.
+    async fn info_installed(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        let pkgs = self.list_installed(kws, flags).await?;
+        // The world file lists the explicitly installed packages, possibly with
+        // version constraints and repository tags attached.
+        let Ok(world) = fs::read_to_string(WORLD_FILE) else {
+            return Ok(pkgs);
+        };
+        let world: HashSet<_> = world
+            .split_whitespace()
+            .filter_map(|dep| dep.split(['<', '>', '=', '~', '@']).next())
+            .collect();
+        Ok(pkgs
+            .into_iter()
+            .map(|pkg| {
+                let explicit = world.contains(pkg.name.as_str());
+                pkg.explicit(explicit)
+            })
+            .collect())
+    }
+
+    /// Lists the packages which have an update available as [`Package`]
+    /// records, aka `Qu`.
+    async fn list_upgrades(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        let out = Cmd::new(["apk", "version", "-l", "<"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await?;
+        Ok(out
+            .lines()
+            .filter_map(|ln| {
+                let (pkgver, new_ver) = ln.split_once('<')?;
+                parse_pkgver(pkgver.trim()).map(|pkg| pkg.new_version(new_ver.trim()))
+            })
+            .collect())
+    }
+
+    /// Searches the sync sources for packages matching all the given
+    /// keywords, aka `Ss`.
+    async fn search(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new(["apk", "search", "-v"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| parse_apk_list(&out))
+    }
+
+    /// Gets the [`Package`] records of the given packages from the sync
+    /// sources, aka `Si`.
+    async fn info(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new(["apk", "search", "-v", "-e"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| parse_apk_list(&out))
+    }
+
+    /// Gets the installed version of the given package, or `None` if it is not
+    /// installed, as used by `T`.
+    async fn installed_version(&self, name: &str, flags: &[&str]) -> Result<Option<String>> {
+        let exists = Cmd::new(["apk", "info", "-e"])
+            .kws([name])
+            .flags(flags)
+            .pipe(|cmd| self.try_output_str(cmd))
+            .await?;
+        if exists.is_none() {
+            return Ok(None);
+        }
+        Ok(self
+            .list_installed(&[name], flags)
+            .await?
+            .into_iter()
+            .find_map(|pkg| pkg.version))
+    }
+
+    /// Dd marks packages as non-explicitly installed, aka `--asdeps`.
+    // `apk` keeps the explicitly installed packages in the world file, so we
+    // just remove the corresponding entries from it.
+    async fn dd(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        if !Path::new(WORLD_FILE).exists() {
+            Cmd::with_sudo(["touch", WORLD_FILE])
+                .pipe(|cmd| self.run(cmd))
+                .await?;
+        }
+        let names = kws.iter().map(|kw| exec::sed_escape(kw)).join("|");
+        Cmd::with_sudo([
+            "sed",
+            "-i",
+            "-E",
+            &format!("/^({names})([<>=~@].*)?$/d"),
+            WORLD_FILE,
+        ])
+        .flags(flags)
+        .pipe(|cmd| self.run(cmd))
+        .await
+    }
+
+    /// De marks packages as explicitly installed, aka `--asexplicit`.
+    async fn de(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        Cmd::with_sudo(["apk", "add"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run(cmd))
+            .await
+    }
+
+    /// F queries the packages from the sync sources which provide FILE.
+    // `apk` only indexes the commands and the shared libraries provided by the
+    // packages, so we look for them by the file name.
+    async fn f(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        let provides = kws
+            .iter()
+            .map(|kw| {
+                let file = kw.rsplit('/').next().unwrap_or(kw);
+                if file.contains(".so") {
+                    format!("so:{file}")
+                } else {
+                    format!("cmd:{file}")
+                }
+            })
+            .collect_vec();
+        Cmd::new(["apk", "search"])
+            .kws(provides)
+            .flags(flags)
+            .pipe(|cmd| self.run(cmd))
+            .await
+    }
+
+    /// Fy refreshes the local files database.
+    async fn fy(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::with_sudo(["apk", "update"]).flags(flags))
+            .await?;
+        if !kws.is_empty() {
+            self.f(kws, flags).await?;
+        }
+        Ok(())
+    }
+
     /// Q generates a list of installed packages.
     async fn q(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
-        if kws.is_empty() {
-            self.run(Cmd::new(["apk", "info"]).flags(flags)).await
-        } else {
-            self.qs(kws, flags).await
+        self.print_installed(kws, flags).await
+    }
+
+    /// Qd lists packages installed as dependencies (not explicitly).
+    async fn qd(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.print_installed_deps(kws, flags).await
+    }
+
+    /// Qdt lists orphans, i.e. packages installed as dependencies but no
+    /// longer required by any installed package.
+    async fn qdt(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        // Packages removed from the world file stay installed until the next
+        // `apk` transaction, so they are orphans unless something requires them.
+        let deps = self
+            .info_installed(kws, flags)
+            .await?
+            .into_iter()
+            .filter(|pkg| pkg.explicit == Some(false))
+            .collect_vec();
+        if deps.is_empty() {
+            return Ok(());
         }
+        let names = deps.iter().map(|pkg| pkg.name.as_str()).collect_vec();
+        let required = Cmd::new(["apk", "info", "-r"])
+            .kws(&names)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| parse_required_by(&out))?;
+        deps.iter()
+            .filter(|pkg| !required.contains(&pkg.name))
+            .for_each(|pkg| self.print_package(pkg, Package::to_q_line));
+        Ok(())
+    }
+
+    /// Qh lists packages held back from upgrades, aka `Q --held`.
+    async fn qh(&self, _kws: &[&str], _flags: &[&str]) -> Result<()> {
+        // Pinned packages are listed in the world file with version constraints.
+        fs::read_to_string(WORLD_FILE)?
+            .split_whitespace()
+            .filter(|dep| dep.contains(['=', '<', '>', '~']))
+            .for_each(|dep| println!("{dep}"));
+        Ok(())
     }
 
     /// Qi displays local package information: name, version, description, etc.
     async fn qi(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
-        self.si(kws, flags).await
+        self.run(Cmd::new(["apk", "info", "-a"]).kws(kws).flags(flags))
+            .await
     }
 
     /// Qii displays local packages which require X to be installed, aka local
@@ -94,13 +360,15 @@ impl Pm for Apk {
     // when including multiple search terms, only packages with descriptions
     // matching ALL of those terms are returned.
     async fn qs(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
-        self.search_regex(Cmd::new(["apk", "info", "-d"]).flags(flags), kws)
-            .await
+        self.print_installed_search(kws, flags).await
     }
 
     /// Qu lists packages which have an update available.
     //? Is that the right way to input '<'?
-    async fn qu(&self, _kws: &[&str], flags: &[&str]) -> Result<()> {
+    async fn qu(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        if self.cfg.names_only {
+            return self.print_upgrades(kws, flags).await;
+        }
         self.run(Cmd::new(["apk", "version", "-l", "<"]).flags(flags))
             .await
     }
@@ -190,12 +458,16 @@ impl Pm for Apk {
     /// Ss searches for package(s) by searching the expression in name,
     /// description, short description.
     async fn ss(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        if self.cfg.names_only {
+            return self.print_search(kws, flags).await;
+        }
         self.run(Cmd::new(["apk", "search", "-v"]).kws(kws).flags(flags))
             .await
     }
 
     /// Su updates outdated packages.
     async fn su(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.pin_ignored().await?;
         Cmd::with_sudo(if kws.is_empty() {
             &["apk", "upgrade"][..]
         } else {
@@ -210,6 +482,7 @@ impl Pm for Apk {
     /// Suy refreshes the local package database, then updates outdated
     /// packages.
     async fn suy(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.pin_ignored().await?;
         Cmd::with_sudo(if kws.is_empty() {
             ["apk", "upgrade", "-U", "-a"]
         } else {
@@ -241,6 +514,12 @@ impl Pm for Apk {
         Ok(())
     }
 
+    /// T prints the dependencies which are not satisfied by the installed
+    /// packages.
+    async fn t(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.deptest(kws, flags).await
+    }
+
     /// U upgrades or adds package(s) to the system and installs the required
     /// dependencies from sync repositories.
     async fn u(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
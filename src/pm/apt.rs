@@ -1,13 +1,21 @@
 #![doc = doc_self!()]
 
-use std::sync::LazyLock;
+use std::{collections::HashSet, sync::LazyLock};
 
 use async_trait::async_trait;
 use indoc::indoc;
+use itertools::Itertools;
+use regex::Regex;
 use tap::prelude::*;
 
 use super::{NoCacheStrategy, Pm, PmHelper, PromptStrategy, Strategy};
-use crate::{config::Config, error::Result, exec::Cmd};
+use crate::{
+    config::Config,
+    error::{Failure, Result},
+    exec::Cmd,
+    lock::Lock,
+    package::Package,
+};
 
 macro_rules! doc_self {
     () => {
@@ -35,6 +43,111 @@ static STRAT_INSTALL: LazyLock<Strategy> = LazyLock::new(|| Strategy {
     ..Strategy::default()
 });
 
+/// The `dpkg-query` output format used to build [`Package`] records.
+const DPKG_QUERY_FORMAT: &str =
+    "${Package}\\t${Version}\\t${db:Status-Status}\\t${Installed-Size}\\t${binary:Summary}\\n";
+
+/// Parses the output of `dpkg-query` in [`DPKG_QUERY_FORMAT`], skipping the
+/// packages that are not (fully) installed.
+fn parse_dpkg_query(out: &str) -> Vec<Package> {
+    out.lines()
+        .filter_map(|ln| {
+            let (name, version, status, size_kib, summary) = ln.split('\t').collect_tuple()?;
+            (status == "installed").then(|| {
+                let pkg = Package::new(name)
+                    .version(version)
+                    .description(summary)
+                    .installed(true);
+                match size_kib.parse::<u64>() {
+                    Ok(size_kib) => pkg.size(size_kib * 1024),
+                    Err(_) => pkg,
+                }
+            })
+        })
+        .collect()
+}
+
+/// The `dpkg-query` output format used to find out the packages which are
+/// required by others.
+const DPKG_QUERY_DEPS_FORMAT: &str = "${Package}\\t${Version}\\t${db:Status-Status}\\t${Essential}\\t${Priority}\\t${Provides}\\t${Pre-Depends}, ${Depends}, ${Recommends}\\n";
+
+/// Parses the output of `dpkg-query` in [`DPKG_QUERY_DEPS_FORMAT`], returning
+/// the installed packages that are neither required nor recommended by any
+/// other installed package, in the style of `deborphan`.
+///
+/// Just like `deborphan`, the essential packages and the ones with a
+/// `required` or `important` priority are never reported.
+fn parse_unrequired(out: &str) -> Vec<Package> {
+    /// Gets the package names in a relationship field such as `Depends`,
+    /// ignoring the version constraints and the architecture qualifiers.
+    fn rel_names(field: &str) -> impl Iterator<Item = &str> {
+        field.split([',', '|']).filter_map(|rel| {
+            let name = rel.trim().split([' ', '(', ':']).next()?;
+            (!name.is_empty()).then_some(name)
+        })
+    }
+
+    let rows = out
+        .lines()
+        .filter_map(|ln| ln.split('\t').collect_tuple::<(_, _, _, _, _, _, _)>())
+        .filter(|&(_, _, status, ..)| status == "installed")
+        .collect_vec();
+    let required: HashSet<_> = rows
+        .iter()
+        .flat_map(|&(.., deps)| rel_names(deps))
+        .collect();
+    rows.into_iter()
+        .filter(|&(name, _, _, essential, priority, provides, _)| {
+            essential != "yes"
+                && !["required", "important"].contains(&priority)
+                && !required.contains(name)
+                && !rel_names(provides).any(|it| required.contains(it))
+        })
+        .map(|(name, version, ..)| Package::new(name).version(version).installed(true))
+        .collect()
+}
+
+/// Parses the output of `apt list --upgradable`.
+fn parse_apt_upgradable(out: &str) -> Vec<Package> {
+    static UPGRADABLE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^([^/\s]+)/(\S+)\s+(\S+)\s+\S+\s+\[upgradable from: ([^\]]+)\]")
+            .expect("ill-formed regex for `apt list --upgradable`")
+    });
+    out.lines()
+        .filter_map(|ln| UPGRADABLE.captures(ln))
+        .map(|caps| {
+            Package::new(&caps[1])
+                .repo(&caps[2])
+                .new_version(&caps[3])
+                .version(&caps[4])
+        })
+        .collect()
+}
+
+/// Parses the output of `apt-cache show`, keeping the first stanza of each
+/// package.
+fn parse_apt_cache_show(out: &str) -> Vec<Package> {
+    out.split("\n\n")
+        .filter_map(|stanza| {
+            let field = |key: &str| {
+                stanza.lines().find_map(|ln| {
+                    let val = ln.strip_prefix(key)?.strip_prefix(':')?;
+                    Some(val.trim().to_owned())
+                })
+            };
+            Some(Package {
+                version: field("Version"),
+                description: field("Description").or_else(|| field("Description-en")),
+                size: field("Installed-Size")
+                    .and_then(|size_kib| size_kib.parse::<u64>().ok())
+                    .map(|size_kib| size_kib * 1024),
+                ..Package::new(field("Package")?)
+            })
+        })
+        .unique_by(|pkg| pkg.name.clone())
+        .collect()
+}
+
 impl Apt {
     #[must_use]
     #[allow(missing_docs)]
@@ -50,6 +163,37 @@ impl Apt {
             .as_deref()
             .expect("default package manager should have been assigned before initialization")
     }
+
+    /// Prints the installed packages not required by any other installed
+    /// package, in the style of `pacman -Qt`.
+    ///
+    /// If `auto_only` is set, only the packages marked as automatically
+    /// installed (i.e. the orphans) are printed, in the style of
+    /// `pacman -Qdt`.
+    async fn print_unrequired(&self, kws: &[&str], flags: &[&str], auto_only: bool) -> Result<()> {
+        let out = Cmd::new(["dpkg-query", "-W", "-f", DPKG_QUERY_DEPS_FORMAT])
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await?;
+        let auto = if auto_only {
+            Some(
+                self.check_output_str(Cmd::new(["apt-mark", "showauto"]))
+                    .await?,
+            )
+        } else {
+            None
+        };
+        let auto: Option<HashSet<_>> = auto.as_deref().map(|auto| auto.lines().collect());
+        parse_unrequired(&out)
+            .iter()
+            .filter(|pkg| kws.is_empty() || kws.contains(&pkg.name.as_str()))
+            .filter(|pkg| {
+                auto.as_ref()
+                    .is_none_or(|auto| auto.contains(pkg.name.as_str()))
+            })
+            .for_each(|pkg| self.print_package(pkg, Package::to_q_line));
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -63,27 +207,201 @@ impl Pm for Apt {
         &self.cfg
     }
 
-    /// Q generates a list of installed packages.
-    async fn q(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
-        Cmd::new(["apt", "list", "--installed"])
+    fn locks(&self) -> &'static [Lock] {
+        &[
+            Lock::File("/var/lib/dpkg/lock-frontend"),
+            Lock::File("/var/lib/dpkg/lock"),
+            Lock::File("/var/lib/apt/lists/lock"),
+            Lock::File("/var/cache/apt/archives/lock"),
+        ]
+    }
+
+    fn failures(&self) -> &'static [(Failure, &'static str)] {
+        &[
+            (Failure::PermissionDenied, r"are you root\?"),
+            (
+                Failure::LockHeld,
+                r"could not get lock|is another process using it\?",
+            ),
+            (
+                Failure::PackageNotFound,
+                r"^E: (unable to locate package|package '.+' has no installation candidate)",
+            ),
+            (
+                Failure::Network,
+                r"temporary failure resolving|could not connect to|unable to connect to",
+            ),
+            (Failure::DiskFull, r"you don't have enough free space"),
+            (
+                Failure::PackageConflict,
+                r": (conflicts|breaks): |trying to overwrite .*, which is also in package",
+            ),
+        ]
+    }
+
+    /// Lists the installed packages as [`Package`] records, aka `Q`.
+    async fn list_installed(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new(["dpkg-query", "-W", "-f", DPKG_QUERY_FORMAT])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| parse_dpkg_query(&out))
+    }
+
+    /// Gets the [`Package`] records of the given installed packages, aka `Qi`.
+    async fn info_installed(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        let pkgs = self.list_installed(kws, flags).await?;
+        let manual = self
+            .check_output_str(Cmd::new(["apt-mark", "showmanual"]).kws(kws))
+            .await?;
+        let manual: HashSet<_> = manual.lines().collect();
+        Ok(pkgs
+            .into_iter()
+            .map(|pkg| {
+                let explicit = manual.contains(pkg.name.as_str());
+                pkg.explicit(explicit)
+            })
+            .collect())
+    }
+
+    /// Lists the packages which have an update available as [`Package`]
+    /// records, aka `Qu`.
+    async fn list_upgrades(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new(["apt", "list", "--upgradable"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| parse_apt_upgradable(&out))
+    }
+
+    /// Searches the sync sources for packages matching all the given
+    /// keywords, aka `Ss`.
+    async fn search(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        let out = Cmd::new(["apt-cache", "search"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await?;
+        Ok(out
+            .lines()
+            .filter_map(|ln| ln.split_once(" - "))
+            .map(|(name, desc)| Package::new(name).description(desc))
+            .collect())
+    }
+
+    /// Gets the [`Package`] records of the given packages from the sync
+    /// sources, aka `Si`.
+    async fn info(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new(["apt-cache", "show"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| parse_apt_cache_show(&out))
+    }
+
+    /// Gets the installed version of the given package, or `None` if it is not
+    /// installed, as used by `T`.
+    async fn installed_version(&self, name: &str, flags: &[&str]) -> Result<Option<String>> {
+        let out = Cmd::new(["dpkg-query", "-W", "-f", "${Version}\\t${db:Status-Status}"])
+            .kws([name])
+            .flags(flags)
+            .pipe(|cmd| self.try_output_str(cmd))
+            .await?;
+        Ok(out.and_then(|out| {
+            let (version, status) = out.trim().split_once('\t')?;
+            (status == "installed").then(|| version.into())
+        }))
+    }
+
+    /// Dd marks packages as non-explicitly installed, aka `--asdeps`.
+    async fn dd(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        Cmd::with_sudo(["apt-mark", "auto"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run(cmd))
+            .await
+    }
+
+    /// De marks packages as explicitly installed, aka `--asexplicit`.
+    async fn de(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        Cmd::with_sudo(["apt-mark", "manual"])
             .kws(kws)
             .flags(flags)
             .pipe(|cmd| self.run(cmd))
             .await
     }
 
+    /// F queries the packages from the sync sources which provide FILE.
+    async fn f(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new(["apt-file", "search"]).kws(kws).flags(flags))
+            .await
+    }
+
+    /// Fl displays files provided by remote package.
+    async fn fl(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new(["apt-file", "list"]).kws(kws).flags(flags))
+            .await
+    }
+
+    /// Fx queries the packages from the sync sources which provide a file
+    /// matching the regex.
+    async fn fx(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(
+            Cmd::new(["apt-file", "search", "--regexp"])
+                .kws(kws)
+                .flags(flags),
+        )
+        .await
+    }
+
+    /// Fy refreshes the local files database.
+    async fn fy(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::with_sudo(["apt-file", "update"]).flags(flags))
+            .await?;
+        if !kws.is_empty() {
+            self.f(kws, flags).await?;
+        }
+        Ok(())
+    }
+
+    /// Q generates a list of installed packages.
+    async fn q(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.print_installed(kws, flags).await
+    }
+
     /// Qc shows the changelog of a package.
     async fn qc(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
         self.run(Cmd::new(["apt", "changelog"]).kws(kws).flags(flags))
             .await
     }
 
+    /// Qd lists packages installed as dependencies (not explicitly).
+    async fn qd(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new(["apt-mark", "showauto"]).kws(kws).flags(flags))
+            .await
+    }
+
+    /// Qdt lists orphans, i.e. packages installed as dependencies but no
+    /// longer required by any installed package.
+    async fn qdt(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.print_unrequired(kws, flags, true).await
+    }
+
     /// Qe lists packages installed explicitly (not as dependencies).
     async fn qe(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
         self.run(Cmd::new(["apt-mark", "showmanual"]).kws(kws).flags(flags))
             .await
     }
 
+    /// Qh lists packages held back from upgrades, aka `Q --held`.
+    async fn qh(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new(["apt-mark", "showhold"]).kws(kws).flags(flags))
+            .await
+    }
+
     /// Qi displays local package information: name, version, description, etc.
     async fn qi(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
         self.run(Cmd::new(["dpkg-query", "-s"]).kws(kws).flags(flags))
@@ -114,14 +432,19 @@ impl Pm for Apt {
     // when including multiple search terms, only packages with descriptions
     // matching ALL of those terms are returned.
     async fn qs(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
-        Cmd::new(["dpkg-query", "-l"])
-            .flags(flags)
-            .pipe(|cmd| self.search_regex_with_header(cmd, kws, 4))
-            .await
+        self.print_installed_search(kws, flags).await
+    }
+
+    /// Qt lists packages not required by any installed package.
+    async fn qt(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.print_unrequired(kws, flags, false).await
     }
 
     /// Qu lists packages which have an update available.
     async fn qu(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        if self.cfg.names_only {
+            return self.print_upgrades(kws, flags).await;
+        }
         Cmd::with_sudo(["apt", "upgrade", "--trivial-only"])
             .kws(kws)
             .flags(flags)
@@ -230,13 +553,21 @@ impl Pm for Apt {
     /// Ss searches for package(s) by searching the expression in name,
     /// description, short description.
     async fn ss(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        if self.cfg.names_only {
+            return self.print_search(kws, flags).await;
+        }
         self.run(Cmd::new([self.cmd(), "search"]).kws(kws).flags(flags))
             .await
     }
 
     /// Su updates outdated packages.
+    // Unlike `pacman --ignore`, the `apt-mark hold` of the ignored packages is
+    // not undone afterwards, so they are kept back from later upgrades as well
+    // until `apt-mark unhold` is used.
     async fn su(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
         if kws.is_empty() {
+            self.hold_ignored(Cmd::with_sudo(["apt-mark", "hold"]))
+                .await?;
             Cmd::with_sudo(["apt", "upgrade"])
                 .flags(flags)
                 .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_PROMPT))
@@ -276,4 +607,10 @@ impl Pm for Apt {
         }
         Ok(())
     }
+
+    /// T prints the dependencies which are not satisfied by the installed
+    /// packages.
+    async fn t(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.deptest(kws, flags).await
+    }
 }
@@ -4,10 +4,17 @@ use std::sync::LazyLock;
 
 use async_trait::async_trait;
 use indoc::indoc;
+use itertools::chain;
+use serde::Deserialize;
 use tap::prelude::*;
 
 use super::{DryRunStrategy, NoCacheStrategy, Pm, PmHelper, PromptStrategy, Strategy};
-use crate::{config::Config, error::Result, exec::Cmd};
+use crate::{
+    config::Config,
+    error::{Failure, Result},
+    exec::Cmd,
+    package::Package,
+};
 
 macro_rules! doc_self {
     () => {
@@ -35,12 +42,136 @@ static STRAT_INSTALL: LazyLock<Strategy> = LazyLock::new(|| Strategy {
     ..Strategy::default()
 });
 
+/// The output of `brew info --json=v2`.
+#[derive(Debug, Deserialize)]
+struct InfoJson {
+    #[serde(default)]
+    formulae: Vec<FormulaJson>,
+    #[serde(default)]
+    casks: Vec<CaskJson>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FormulaJson {
+    name: String,
+    tap: Option<String>,
+    desc: Option<String>,
+    versions: FormulaVersionsJson,
+    #[serde(default)]
+    installed: Vec<FormulaInstalledJson>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FormulaVersionsJson {
+    stable: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FormulaInstalledJson {
+    version: String,
+    #[serde(default)]
+    installed_on_request: bool,
+    installed_as_dependency: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CaskJson {
+    token: String,
+    tap: Option<String>,
+    desc: Option<String>,
+    version: Option<String>,
+    installed: Option<String>,
+}
+
+impl InfoJson {
+    /// Converts the output into [`Package`] records, using the installed
+    /// versions if `installed` is `true`, and the available ones otherwise.
+    fn into_packages(self, installed: bool) -> Vec<Package> {
+        let formulae = self.formulae.into_iter().filter_map(|f| {
+            let (version, explicit) = if installed {
+                let keg = f.installed.last()?;
+                let explicit =
+                    keg.installed_on_request || keg.installed_as_dependency == Some(false);
+                (Some(keg.version.clone()), Some(explicit))
+            } else {
+                (f.versions.stable, None)
+            };
+            Some(Package {
+                version,
+                repo: f.tap,
+                description: f.desc,
+                installed,
+                explicit,
+                ..Package::new(f.name)
+            })
+        });
+        let casks = self.casks.into_iter().filter_map(|c| {
+            let version = if installed {
+                Some(c.installed?)
+            } else {
+                c.version
+            };
+            // Casks are never installed as dependencies.
+            Some(Package {
+                version,
+                repo: c.tap,
+                description: c.desc,
+                installed,
+                explicit: installed.then_some(true),
+                ..Package::new(c.token)
+            })
+        });
+        chain!(formulae, casks).collect()
+    }
+}
+
+/// The output of `brew outdated --json=v2`.
+#[derive(Debug, Deserialize)]
+struct OutdatedJson {
+    #[serde(default)]
+    formulae: Vec<OutdatedItemJson>,
+    #[serde(default)]
+    casks: Vec<OutdatedItemJson>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OutdatedItemJson {
+    name: String,
+    /// Either a list of versions or, for older casks, a single version.
+    installed_versions: serde_json::Value,
+    current_version: String,
+}
+
+impl OutdatedItemJson {
+    fn into_package(self) -> Package {
+        let version = match self.installed_versions {
+            serde_json::Value::Array(vs) => vs.last().and_then(|v| v.as_str()).map(Into::into),
+            serde_json::Value::String(v) => Some(v),
+            _ => None,
+        };
+        Package {
+            version,
+            ..Package::new(self.name).new_version(self.current_version)
+        }
+    }
+}
+
 impl Brew {
     #[must_use]
     #[allow(missing_docs)]
     pub const fn new(cfg: Config) -> Self {
         Self { cfg }
     }
+
+    /// Runs `brew info --json=v2` with the given arguments.
+    async fn info_json(&self, args: &[&str], kws: &[&str], flags: &[&str]) -> Result<InfoJson> {
+        let out = Cmd::new(["brew", "info", "--json=v2"])
+            .kws(args.iter().chain(kws))
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await?;
+        Ok(serde_json::from_str(&out)?)
+    }
 }
 
 #[async_trait]
@@ -54,13 +185,129 @@ impl Pm for Brew {
         &self.cfg
     }
 
+    fn failures(&self) -> &'static [(Failure, &'static str)] {
+        &[
+            (
+                Failure::LockHeld,
+                r"another active homebrew .+ process is already in progress|has already locked",
+            ),
+            (
+                Failure::PackageNotFound,
+                r"no available formula|no formulae or casks found|no cask with this name",
+            ),
+            (
+                Failure::Network,
+                r"failed to connect to|could not resolve host",
+            ),
+            (
+                Failure::PackageConflict,
+                r"cannot install .+ because conflicting formulae are installed",
+            ),
+        ]
+    }
+
+    /// Lists the installed packages as [`Package`] records, aka `Q`.
+    async fn list_installed(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        let args: &[&str] = if kws.is_empty() {
+            &["--installed"]
+        } else {
+            &[]
+        };
+        self.info_json(args, kws, flags)
+            .await
+            .map(|info| info.into_packages(true))
+    }
+
+    /// Gets the [`Package`] records of the given installed packages, aka `Qi`.
+    async fn info_installed(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        self.list_installed(kws, flags).await
+    }
+
+    /// Lists the explicitly installed packages as [`Package`] records, aka
+    /// `Qe`.
+    // The packages from taps other than `homebrew/core` are named after their
+    // taps, e.g. `homebrew/cask/firefox`, so that `brew install` can tell casks
+    // and formulae apart.
+    async fn list_explicit(&self, flags: &[&str]) -> Result<Vec<Package>> {
+        Ok(self
+            .list_installed(&[], flags)
+            .await?
+            .into_iter()
+            .filter(|pkg| pkg.explicit == Some(true))
+            .map(|pkg| match pkg.repo.as_deref() {
+                Some(tap) if tap != "homebrew/core" => Package {
+                    name: format!("{tap}/{}", pkg.name),
+                    ..pkg
+                },
+                _ => pkg,
+            })
+            .collect())
+    }
+
+    /// Lists the packages which have an update available as [`Package`]
+    /// records, aka `Qu`.
+    async fn list_upgrades(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        let out = Cmd::new(["brew", "outdated", "--json=v2"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await?;
+        let outdated: OutdatedJson = serde_json::from_str(&out)?;
+        Ok(chain!(outdated.formulae, outdated.casks)
+            .map(OutdatedItemJson::into_package)
+            .collect())
+    }
+
+    /// Searches the sync sources for packages matching all the given
+    /// keywords, aka `Ss`.
+    async fn search(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        let out = Cmd::new(["brew", "search"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await?;
+        Ok(out
+            .lines()
+            .map(str::trim)
+            .filter(|ln| !(ln.is_empty() || ln.starts_with("==>")))
+            .map(Package::new)
+            .collect())
+    }
+
+    /// Gets the [`Package`] records of the given packages from the sync
+    /// sources, aka `Si`.
+    async fn info(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        self.info_json(&[], kws, flags)
+            .await
+            .map(|info| info.into_packages(false))
+    }
+
+    /// Gets the installed version of the given package, or `None` if it is not
+    /// installed, as used by `T`.
+    async fn installed_version(&self, name: &str, flags: &[&str]) -> Result<Option<String>> {
+        Cmd::new(["brew", "list", "--versions"])
+            .kws([name])
+            .flags(flags)
+            .pipe(|cmd| self.try_output_str(cmd))
+            .await
+            .map(|out| out?.split_whitespace().skip(1).last().map(Into::into))
+    }
+
+    /// F queries the packages from the sync sources which provide FILE.
+    // `brew which-formula` only knows about the executables, which are looked up
+    // by the file name.
+    async fn f(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        let files = kws.iter().map(|kw| kw.rsplit('/').next().unwrap_or(kw));
+        Cmd::new(["brew", "which-formula"])
+            .kws(files)
+            .flags(flags)
+            .pipe(|cmd| self.run(cmd))
+            .await
+    }
+
     /// Q generates a list of installed packages.
     async fn q(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
-        if kws.is_empty() {
-            self.run(Cmd::new(["brew", "list"]).flags(flags)).await
-        } else {
-            self.qs(kws, flags).await
-        }
+        self.print_installed(kws, flags).await
     }
 
     /// Qc shows the changelog of a package.
@@ -69,9 +316,35 @@ impl Pm for Brew {
             .await
     }
 
+    /// Qd lists packages installed as dependencies (not explicitly).
+    async fn qd(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.print_installed_deps(kws, flags).await
+    }
+
+    /// Qdt lists orphans, i.e. packages installed as dependencies but no
+    /// longer required by any installed package.
+    async fn qdt(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        Cmd::new(["brew", "autoremove", "--dry-run"])
+            .flags(flags)
+            .pipe(|cmd| self.search_names(cmd, kws, |ln| Some(ln)))
+            .await
+    }
+
+    /// Qe lists packages installed explicitly (not as dependencies).
+    async fn qe(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.print_explicit(kws, flags).await
+    }
+
+    /// Qh lists packages held back from upgrades, aka `Q --held`.
+    async fn qh(&self, _kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new(["brew", "list", "--pinned"]).flags(flags))
+            .await
+    }
+
     /// Qi displays local package information: name, version, description, etc.
     async fn qi(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
-        self.si(kws, flags).await
+        self.run(Cmd::new(["brew", "info"]).kws(kws).flags(flags))
+            .await
     }
 
     /// Qii displays local packages which require X to be installed, aka local
@@ -98,15 +371,15 @@ impl Pm for Brew {
     // when including multiple search terms, only packages with descriptions
     // matching ALL of those terms are returned.
     async fn qs(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
-        // ! `brew list` lists all formulae and casks only when using tty.
-        self.search_regex(Cmd::new(["brew", "list", "--formula"]).flags(flags), kws)
-            .await?;
-        if cfg!(target_os = "macos") {
-            self.search_regex(Cmd::new(["brew", "list", "--cask"]).flags(flags), kws)
-                .await?;
-        }
+        self.print_installed_search(kws, flags).await
+    }
 
-        Ok(())
+    /// Qt lists packages not required by any installed package.
+    async fn qt(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        Cmd::new(["brew", "leaves"])
+            .flags(flags)
+            .pipe(|cmd| self.search_names(cmd, kws, |ln| Some(ln)))
+            .await
     }
 
     /// Qu lists packages which have an update available.
@@ -233,12 +506,16 @@ impl Pm for Brew {
     /// Ss searches for package(s) by searching the expression in name,
     /// description, short description.
     async fn ss(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        if self.cfg.names_only {
+            return self.print_search(kws, flags).await;
+        }
         self.run(Cmd::new(["brew", "search"]).kws(kws).flags(flags))
             .await
     }
 
     /// Su updates outdated packages.
     async fn su(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.hold_ignored(Cmd::new(["brew", "pin"])).await?;
         Cmd::new(["brew", "upgrade"])
             .kws(kws)
             .flags(flags)
@@ -271,4 +548,10 @@ impl Pm for Brew {
         }
         Ok(())
     }
+
+    /// T prints the dependencies which are not satisfied by the installed
+    /// packages.
+    async fn t(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.deptest(kws, flags).await
+    }
 }
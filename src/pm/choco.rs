@@ -7,7 +7,7 @@ use indoc::indoc;
 use tap::prelude::*;
 
 use super::{DryRunStrategy, Pm, PmHelper, PromptStrategy, Strategy};
-use crate::{config::Config, error::Result, exec::Cmd};
+use crate::{config::Config, error::Result, exec::Cmd, package::Package};
 
 macro_rules! doc_self {
     () => {
@@ -60,6 +60,54 @@ impl Pm for Choco {
         &self.cfg
     }
 
+    /// Lists the packages which have an update available as [`Package`]
+    /// records, aka `Qu`.
+    async fn list_upgrades(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new(["choco", "outdated", "--limit-output"])
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| {
+                out.lines()
+                    .filter_map(|ln| {
+                        // E.g. `git|2.44.0|2.45.1|false`, the last field being
+                        // whether the package is pinned.
+                        let mut fields = ln.trim().split('|');
+                        let (name, version) = (fields.next()?, fields.next()?);
+                        Some(
+                            Package::new(name)
+                                .version(version)
+                                .new_version(fields.next()?)
+                                .installed(true),
+                        )
+                    })
+                    .filter(|pkg| kws.is_empty() || kws.contains(&pkg.name.as_str()))
+                    .collect()
+            })
+    }
+
+    /// Lists the explicitly installed packages as [`Package`] records, aka
+    /// `Qe`.
+    // `choco` does not tell the dependencies apart, so every installed package
+    // is listed.
+    async fn list_explicit(&self, flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new(["choco", "list", "--limit-output"])
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| {
+                out.lines()
+                    .filter_map(|ln| ln.trim().split_once('|'))
+                    .map(|(name, version)| {
+                        Package::new(name)
+                            .version(version)
+                            .installed(true)
+                            .explicit(true)
+                    })
+                    .collect()
+            })
+    }
+
     /// Q generates a list of installed packages.
     async fn q(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
         Cmd::new(["choco", "list"])
@@ -5,10 +5,11 @@ use std::sync::LazyLock;
 use async_trait::async_trait;
 use futures::prelude::*;
 use indoc::indoc;
+use serde::Deserialize;
 use tap::prelude::*;
 
 use super::{Pm, PmHelper, PromptStrategy, Strategy};
-use crate::{config::Config, error::Result, exec::Cmd};
+use crate::{config::Config, error::Result, exec::Cmd, package::Package};
 
 macro_rules! doc_self {
     () => {
@@ -30,6 +31,48 @@ static STRAT_PROMPT: LazyLock<Strategy> = LazyLock::new(|| Strategy {
     ..Strategy::default()
 });
 
+/// The output of `conda env export --from-history --json`.
+#[derive(Debug, Deserialize)]
+struct EnvExportJson {
+    /// The specs the user has asked for, e.g. `conda-forge::numpy>=1.26`.
+    #[serde(default)]
+    dependencies: Vec<String>,
+}
+
+/// An item in the output of `conda list --json`.
+#[derive(Debug, Deserialize)]
+struct ListItemJson {
+    name: String,
+    version: String,
+    channel: Option<String>,
+}
+
+/// The output of `conda update --all --dry-run --json`, which lacks the
+/// actions if there is nothing to do.
+#[derive(Debug, Deserialize)]
+struct DryRunJson {
+    #[serde(default)]
+    actions: ActionsJson,
+}
+
+/// The packages to be linked and unlinked by a `conda` transaction.
+#[derive(Debug, Default, Deserialize)]
+struct ActionsJson {
+    #[serde(rename = "LINK", default)]
+    link: Vec<ListItemJson>,
+    #[serde(rename = "UNLINK", default)]
+    unlink: Vec<ListItemJson>,
+}
+
+impl From<ListItemJson> for Package {
+    fn from(item: ListItemJson) -> Self {
+        Self {
+            repo: item.channel,
+            ..Self::new(item.name).version(item.version).installed(true)
+        }
+    }
+}
+
 impl Conda {
     #[must_use]
     #[allow(missing_docs)]
@@ -49,6 +92,73 @@ impl Pm for Conda {
         &self.cfg
     }
 
+    /// Lists the installed packages as [`Package`] records, aka `Q`.
+    async fn list_installed(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        let out = Cmd::new(["conda", "list", "--json"])
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await?;
+        let items: Vec<ListItemJson> = serde_json::from_str(&out)?;
+        Ok(items
+            .into_iter()
+            .filter(|item| kws.is_empty() || kws.contains(&item.name.as_str()))
+            .map(Package::from)
+            .collect())
+    }
+
+    /// Lists the packages which have an update available as [`Package`]
+    /// records, aka `Qu`.
+    // The packages upgraded are those both unlinked and linked again by a dry
+    // run of `conda update --all`.
+    async fn list_upgrades(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        let out = Cmd::new(["conda", "update", "--all", "--dry-run", "--json"])
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await?;
+        let DryRunJson { actions } = serde_json::from_str(&out)?;
+        Ok(actions
+            .link
+            .into_iter()
+            .filter(|new| kws.is_empty() || kws.contains(&new.name.as_str()))
+            .filter_map(|new| {
+                let old = actions.unlink.iter().find(|old| old.name == new.name)?;
+                Some(Package {
+                    version: Some(old.version.clone()),
+                    new_version: Some(new.version.clone()),
+                    ..Package::from(new)
+                })
+            })
+            .collect())
+    }
+
+    /// Lists the explicitly installed packages as [`Package`] records, aka
+    /// `Qe`.
+    // The environment history only keeps the specs as they have been typed,
+    // so the versions are looked up in the list of installed packages.
+    async fn list_explicit(&self, flags: &[&str]) -> Result<Vec<Package>> {
+        let out = Cmd::new(["conda", "env", "export", "--from-history", "--json"])
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await?;
+        let export: EnvExportJson = serde_json::from_str(&out)?;
+        let requested: Vec<_> = export
+            .dependencies
+            .iter()
+            .filter_map(|spec| {
+                let spec = spec.rsplit("::").next().unwrap_or(spec);
+                spec.split(|c: char| "=<>!~ [".contains(c))
+                    .next()
+                    .filter(|name| !name.is_empty())
+            })
+            .collect();
+        Ok(self
+            .list_installed(&requested, flags)
+            .await?
+            .into_iter()
+            .map(|pkg| pkg.explicit(true))
+            .collect())
+    }
+
     /// Q generates a list of installed packages.
     async fn q(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
         if kws.is_empty() {
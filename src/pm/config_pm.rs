@@ -0,0 +1,140 @@
+#![doc = doc_self!()]
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use indoc::indoc;
+use serde::{Deserialize, Serialize};
+use tap::prelude::*;
+use tt_call::tt_call;
+
+use super::{Pm, PmHelper, Strategy};
+use crate::{
+    config::Config,
+    error::{Error, Result},
+    exec::Cmd,
+    methods,
+};
+
+macro_rules! doc_self {
+    () => {
+        indoc! {"
+            A package manager defined in the `pms` config item, e.g.
+
+            ```toml
+            [pms.opkg.s]
+            cmd = [\"opkg\", \"install\"]
+            sudo = true
+            dry_run = { with_flags = [\"--noaction\"] }
+
+            [pms.opkg.r]
+            cmd = [\"opkg\", \"remove\"]
+            sudo = true
+            prompt = \"custom_prompt\"
+            ```
+        "}
+    };
+}
+use doc_self;
+
+/// The definition of a `pacman` method of a package manager defined in
+/// [`Config::pms`].
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct MethodDef {
+    /// The command to be run, to which the flags and keywords are appended,
+    /// e.g. `["opkg", "install"]`, unless they are placed with a
+    /// [`MethodDef::KWS`] argument, e.g.
+    /// `["opkg", "install", "{kws}", "--force-depends"]`.
+    pub cmd: Vec<String>,
+
+    /// Whether the command should be run with `sudo`.
+    #[serde(default)]
+    pub sudo: bool,
+
+    /// How the command is run, with the `dry_run`, `prompt` and `no_cache`
+    /// keys.
+    #[serde(flatten)]
+    pub strat: Strategy,
+}
+
+/// The definitions of the `pacman` methods of a package manager defined in
+/// [`Config::pms`], indexed by method name, e.g. `s` or `suy`.
+pub type PmDef = BTreeMap<String, MethodDef>;
+
+impl MethodDef {
+    /// The argument of [`MethodDef::cmd`] replaced by the flags and keywords.
+    pub const KWS: &str = "{kws}";
+}
+
+#[doc = doc_self!()]
+#[derive(Debug)]
+pub struct ConfigPm {
+    name: String,
+    def: PmDef,
+    cfg: Config,
+}
+
+impl ConfigPm {
+    /// Creates a new [`ConfigPm`] with the given name and definition.
+    #[must_use]
+    pub(crate) const fn new(name: String, def: PmDef, cfg: Config) -> Self {
+        Self { name, def, cfg }
+    }
+
+    /// Runs the command defined for the given method.
+    async fn run_method(&self, method: &str, kws: &[&str], flags: &[&str]) -> Result<()> {
+        let Some(def) = self.def.get(method) else {
+            return Err(Error::OperationUnimplementedError {
+                op: method.into(),
+                pm: self.name.clone(),
+            });
+        };
+        let mut parts = def.cmd.splitn(2, |arg| arg == MethodDef::KWS);
+        let (cmd, tail) = (
+            parts.next().unwrap_or_default(),
+            parts.next().unwrap_or_default(),
+        );
+        if cmd.is_empty() {
+            return Err(Error::ConfigError(Box::new(
+                format!("empty `cmd` for `{method}` in `pms.{}`", self.name).into(),
+            )));
+        }
+        Cmd::new(cmd)
+            .tail(tail)
+            .sudo(def.sudo)
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run_with(cmd, self.default_mode(), &def.strat))
+            .await
+    }
+}
+
+macro_rules! impl_config_pm {(
+    methods = [{ $(
+        $( #[$meta:meta] )*
+        async fn $method:ident;
+    )* }]
+) => {
+    #[async_trait]
+    impl Pm for ConfigPm {
+        /// Gets the name of the package manager.
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn cfg(&self) -> &Config {
+            &self.cfg
+        }
+
+        // * Automatically generated methods below... *
+        $( $( #[$meta] )*
+        async fn $method(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+            self.run_method(stringify!($method), kws, flags).await
+        } )*
+    }
+};}
+
+tt_call! {
+    macro = [{ methods }]
+    ~~> impl_config_pm
+}
@@ -1,13 +1,23 @@
 #![doc = doc_self!()]
 
-use std::sync::LazyLock;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::LazyLock,
+};
 
 use async_trait::async_trait;
 use indoc::indoc;
+use itertools::Itertools;
 use tap::prelude::*;
 
 use super::{NoCacheStrategy, Pm, PmHelper, PromptStrategy, Strategy};
-use crate::{config::Config, error::Result, exec::Cmd};
+use crate::{
+    config::Config,
+    error::{Failure, Result},
+    exec::{self, Cmd},
+    lock::Lock,
+    package::Package,
+};
 
 macro_rules! doc_self {
     () => {
@@ -40,12 +50,55 @@ static STRAT_INSTALL: LazyLock<Strategy> = LazyLock::new(|| Strategy {
     ..Strategy::default()
 });
 
+/// The `dnf repoquery` output format used to build [`Package`] records.
+///
+/// The trailing newline is required by `dnf5`, while the extra empty lines
+/// produced by `dnf4` are simply skipped.
+const REPOQUERY_FORMAT: &str = "%{name}\\t%{evr}\\t%{repoid}\\t%{summary}\\n";
+
+/// Parses the output of `dnf repoquery` in [`REPOQUERY_FORMAT`].
+fn parse_repoquery(out: &str) -> Vec<Package> {
+    out.lines()
+        .filter_map(|ln| {
+            let (name, evr, repo, summary) = ln.split('\t').collect_tuple()?;
+            Some(
+                Package::new(name)
+                    .version(evr)
+                    .repo(repo)
+                    .description(summary),
+            )
+        })
+        .collect()
+}
+
 impl Dnf {
     #[must_use]
     #[allow(missing_docs)]
     pub const fn new(cfg: Config) -> Self {
         Self { cfg }
     }
+
+    /// Runs `dnf repoquery` with the given arguments, returning the
+    /// [`Package`] records found.
+    async fn repoquery(&self, args: &[&str], kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new(["dnf", "repoquery", "--qf", REPOQUERY_FORMAT])
+            .kws(args.iter().chain(kws))
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| parse_repoquery(&out))
+    }
+
+    /// Makes a `dnf repoquery` [`Cmd`] with the given arguments, printing
+    /// only the package names if [`Config::names_only`] is set.
+    fn repoquery_cmd(&self, args: &[&str]) -> Cmd {
+        let names_only: &[&str] = if self.cfg.names_only {
+            &["-q", "--qf", "%{name}\\n"]
+        } else {
+            &[]
+        };
+        Cmd::new(["dnf", "repoquery"].iter().chain(names_only).chain(args))
+    }
 }
 
 #[async_trait]
@@ -59,14 +112,197 @@ impl Pm for Dnf {
         &self.cfg
     }
 
-    /// Q generates a list of installed packages.
-    async fn q(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
-        if kws.is_empty() {
-            self.run(Cmd::new(["rpm", "-qa", "--qf", "%{NAME} %{VERSION}\\n"]).flags(flags))
-                .await
+    fn locks(&self) -> &'static [Lock] {
+        &[
+            Lock::PidFile("/var/lib/dnf/rpmdb_lock.pid"),
+            Lock::PidFile("/var/cache/dnf/metadata_lock.pid"),
+            Lock::PidFile("/var/cache/dnf/download_lock.pid"),
+            Lock::File("/var/lib/rpm/.rpm.lock"),
+        ]
+    }
+
+    fn failures(&self) -> &'static [(Failure, &'static str)] {
+        &[
+            (
+                Failure::PermissionDenied,
+                r"has to be run (with superuser privileges|under the root user)",
+            ),
+            (
+                Failure::LockHeld,
+                r"waiting for process with pid \d+|failed to obtain .*lock|another transaction is in progress",
+            ),
+            (
+                Failure::PackageNotFound,
+                r"no match for argument|unable to find a match|no matching packages",
+            ),
+            (
+                Failure::Network,
+                r"curl error \((6|7|28)\)|cannot download repomd\.xml|failed to download metadata",
+            ),
+            (
+                Failure::DiskFull,
+                r"disk requirements:|needs .+ more space on the .+ filesystem",
+            ),
+            (
+                Failure::PackageConflict,
+                r"conflicts with file from package|conflicting requests",
+            ),
+        ]
+    }
+
+    /// Lists the installed packages as [`Package`] records, aka `Q`.
+    async fn list_installed(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        let pkgs = self.repoquery(&["--installed"], kws, flags).await?;
+        Ok(pkgs
+            .into_iter()
+            .map(|mut pkg| {
+                // Installed packages are reported as coming from `@System`.
+                pkg.repo = pkg.repo.filter(|repo| !repo.starts_with('@'));
+                pkg.installed(true)
+            })
+            .collect())
+    }
+
+    /// Gets the [`Package`] records of the given installed packages, aka `Qi`.
+    async fn info_installed(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        let pkgs = self.list_installed(kws, flags).await?;
+        let user_installed: HashSet<_> = self
+            .repoquery(&["--userinstalled"], kws, flags)
+            .await?
+            .into_iter()
+            .map(|pkg| pkg.name)
+            .collect();
+        Ok(pkgs
+            .into_iter()
+            .map(|pkg| {
+                let explicit = user_installed.contains(&pkg.name);
+                pkg.explicit(explicit)
+            })
+            .collect())
+    }
+
+    /// Lists the packages which have an update available as [`Package`]
+    /// records, aka `Qu`.
+    async fn list_upgrades(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        let upgrades = self
+            .repoquery(&["--upgrades", "--latest-limit=1"], kws, flags)
+            .await?;
+        if upgrades.is_empty() {
+            return Ok(upgrades);
+        }
+        let names = upgrades.iter().map(|pkg| pkg.name.as_str()).collect_vec();
+        let installed: HashMap<_, _> = self
+            .list_installed(&names, flags)
+            .await?
+            .into_iter()
+            .map(|pkg| (pkg.name, pkg.version))
+            .collect();
+        Ok(upgrades
+            .into_iter()
+            .map(|pkg| Package {
+                version: installed.get(&pkg.name).cloned().flatten(),
+                new_version: pkg.version,
+                ..pkg
+            })
+            .collect())
+    }
+
+    /// Searches the sync sources for packages matching all the given
+    /// keywords, aka `Ss`.
+    async fn search(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        let out = Cmd::new([
+            "dnf",
+            "repoquery",
+            "--latest-limit=1",
+            "--qf",
+            REPOQUERY_FORMAT,
+        ])
+        .flags(flags)
+        .pipe(|cmd| self.check_output_str(cmd))
+        .await?;
+        Ok(parse_repoquery(&exec::grep(&out, kws)?.join("\n")))
+    }
+
+    /// Gets the [`Package`] records of the given packages from the sync
+    /// sources, aka `Si`.
+    async fn info(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        self.repoquery(&["--latest-limit=1"], kws, flags).await
+    }
+
+    /// Gets the installed version of the given package, or `None` if it is not
+    /// installed, as used by `T`.
+    async fn installed_version(&self, name: &str, flags: &[&str]) -> Result<Option<String>> {
+        Cmd::new([
+            "rpm",
+            "-q",
+            "--qf",
+            "%|EPOCH?{%{EPOCH}:}:{}|%{VERSION}-%{RELEASE}\\n",
+        ])
+        .kws([name])
+        .flags(flags)
+        .pipe(|cmd| self.try_output_str(cmd))
+        .await
+        .map(|out| out?.lines().last().map(Into::into))
+    }
+
+    /// Reverts the last `n` transactions with `dnf history`, as used by
+    /// `--undo`.
+    async fn undo(&self, n: usize, flags: &[&str]) -> Result<()> {
+        // `last-1` is the transaction before the last one, so rolling back to
+        // `last-n` undoes the last `n` transactions.
+        if n == 1 {
+            Cmd::with_sudo(["dnf", "history", "undo"]).kws(["last"])
         } else {
-            self.qs(kws, flags).await
+            Cmd::with_sudo(["dnf", "history", "rollback"]).kws([format!("last-{n}")])
+        }
+        .flags(flags)
+        .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_PROMPT))
+        .await
+    }
+
+    /// Dd marks packages as non-explicitly installed, aka `--asdeps`.
+    async fn dd(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        Cmd::with_sudo(["dnf", "mark", "remove"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run(cmd))
+            .await
+    }
+
+    /// De marks packages as explicitly installed, aka `--asexplicit`.
+    async fn de(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        Cmd::with_sudo(["dnf", "mark", "install"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run(cmd))
+            .await
+    }
+
+    /// F queries the packages from the sync sources which provide FILE.
+    async fn f(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new(["dnf", "provides"]).kws(kws).flags(flags))
+            .await
+    }
+
+    /// Fl displays files provided by remote package.
+    async fn fl(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new(["dnf", "repoquery", "-l"]).kws(kws).flags(flags))
+            .await
+    }
+
+    /// Fy refreshes the local files database.
+    async fn fy(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new(["dnf", "makecache"]).flags(flags))
+            .await?;
+        if !kws.is_empty() {
+            self.f(kws, flags).await?;
         }
+        Ok(())
+    }
+
+    /// Q generates a list of installed packages.
+    async fn q(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.print_installed(kws, flags).await
     }
 
     /// Qc shows the changelog of a package.
@@ -78,15 +314,36 @@ impl Pm for Dnf {
             .await
     }
 
+    /// Qd lists packages installed as dependencies (not explicitly).
+    async fn qd(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.print_installed_deps(kws, flags).await
+    }
+
+    /// Qdt lists orphans, i.e. packages installed as dependencies but no
+    /// longer required by any installed package.
+    async fn qdt(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.repoquery_cmd(&["--unneeded"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run(cmd))
+            .await
+    }
+
     /// Qe lists packages installed explicitly (not as dependencies).
     async fn qe(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
-        Cmd::new(["dnf", "repoquery", "--userinstalled"])
+        self.repoquery_cmd(&["--userinstalled"])
             .kws(kws)
             .flags(flags)
             .pipe(|cmd| self.run(cmd))
             .await
     }
 
+    /// Qh lists packages held back from upgrades, aka `Q --held`.
+    async fn qh(&self, _kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new(["dnf", "versionlock", "list"]).flags(flags))
+            .await
+    }
+
     /// Qi displays local package information: name, version, description, etc.
     async fn qi(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
         Cmd::new(["dnf", "info", "--installed"])
@@ -99,7 +356,7 @@ impl Pm for Dnf {
     /// Qii displays local packages which require X to be installed, aka local
     /// reverse dependencies.
     async fn qii(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
-        Cmd::new(["dnf", "repoquery", "--installed", "--whatdepends"])
+        self.repoquery_cmd(&["--installed", "--whatdepends"])
             .kws(kws)
             .flags(flags)
             .pipe(|cmd| self.run(cmd))
@@ -138,12 +395,20 @@ impl Pm for Dnf {
     // matching ALL of those terms are returned.
     // TODO: Is this right?
     async fn qs(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
-        self.search_regex(Cmd::new(["rpm", "-qa"]).flags(flags), kws)
+        self.print_installed_search(kws, flags).await
+    }
+
+    /// Qt lists packages not required by any installed package.
+    async fn qt(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new(["dnf", "leaves"]).kws(kws).flags(flags))
             .await
     }
 
     /// Qu lists packages which have an update available.
     async fn qu(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        if self.cfg.names_only {
+            return self.print_upgrades(kws, flags).await;
+        }
         self.run(Cmd::new(["dnf", "list", "updates"]).kws(kws).flags(flags))
             .await
     }
@@ -200,7 +465,7 @@ impl Pm for Dnf {
     /// Sii displays packages which require X to be installed, aka reverse
     /// dependencies.
     async fn sii(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
-        Cmd::new(["dnf", "repoquery", "--whatdepends"])
+        self.repoquery_cmd(&["--whatdepends"])
             .kws(kws)
             .flags(flags)
             .pipe(|cmd| self.run(cmd))
@@ -233,12 +498,17 @@ impl Pm for Dnf {
     /// Ss searches for package(s) by searching the expression in name,
     /// description, short description.
     async fn ss(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        if self.cfg.names_only {
+            return self.print_search(kws, flags).await;
+        }
         self.run(Cmd::new(["dnf", "search"]).kws(kws).flags(flags))
             .await
     }
 
     /// Su updates outdated packages.
     async fn su(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.hold_ignored(Cmd::with_sudo(["dnf", "versionlock", "add"]))
+            .await?;
         Cmd::with_sudo(["dnf", "upgrade"])
             .kws(kws)
             .flags(flags)
@@ -273,6 +543,12 @@ impl Pm for Dnf {
         Ok(())
     }
 
+    /// T prints the dependencies which are not satisfied by the installed
+    /// packages.
+    async fn t(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.deptest(kws, flags).await
+    }
+
     /// U upgrades or adds package(s) to the system and installs the required
     /// dependencies from sync repositories.
     async fn u(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
@@ -1,14 +1,15 @@
 #![doc = doc_self!()]
 
-use std::sync::LazyLock;
+use std::{fs, path::Path, sync::LazyLock};
 
 use async_trait::async_trait;
 use indoc::indoc;
 use itertools::Itertools;
+use regex::Regex;
 use tap::prelude::*;
 
-use super::{NoCacheStrategy, Pm, PmHelper, PromptStrategy, Strategy};
-use crate::{config::Config, error::Result, exec::Cmd};
+use super::{DryRunStrategy, NoCacheStrategy, Pm, PmHelper, PmMode, PromptStrategy, Strategy};
+use crate::{config::Config, error::Result, exec::Cmd, package::Package};
 
 macro_rules! doc_self {
     () => {
@@ -25,6 +26,21 @@ pub struct Emerge {
     cfg: Config,
 }
 
+/// The `package.mask` file (or directory) of `portage`.
+const PACKAGE_MASK: &str = "/etc/portage/package.mask";
+
+/// The file masking the upgrades of the packages held back by `pacaptr`, if
+/// [`PACKAGE_MASK`] is a directory.
+const PACKAGE_MASK_FILE: &str = "/etc/portage/package.mask/pacaptr";
+
+/// The `world` file of `portage`, listing the explicitly installed packages.
+const WORLD_FILE: &str = "/var/lib/portage/world";
+
+static STRAT_QUERY: LazyLock<Strategy> = LazyLock::new(|| Strategy {
+    dry_run: DryRunStrategy::WithFlags(vec![]),
+    ..Strategy::default()
+});
+
 static STRAT_ASK: LazyLock<Strategy> = LazyLock::new(|| Strategy {
     prompt: PromptStrategy::native_confirm(["--ask"]),
     ..Strategy::default()
@@ -47,6 +63,49 @@ impl Emerge {
     pub const fn new(cfg: Config) -> Self {
         Self { cfg }
     }
+
+    /// Returns the file in which the package masks are written.
+    fn mask_file() -> &'static str {
+        if Path::new(PACKAGE_MASK).is_file() {
+            PACKAGE_MASK
+        } else {
+            PACKAGE_MASK_FILE
+        }
+    }
+
+    /// Masks the versions newer than the installed ones of the packages
+    /// returned by [`PmHelper::ignored`], so that they are held back from
+    /// upgrades.
+    async fn mask_ignored(&self) -> Result<()> {
+        let mask_file = Self::mask_file();
+        let masked = fs::read_to_string(mask_file).unwrap_or_default();
+        let mut masks = vec![];
+        for name in self.ignored()? {
+            // This gives the installed `category/name-version`, if any.
+            let cpv = Cmd::new(["portageq", "best_version", "/", name])
+                .pipe(|cmd| self.check_output(cmd, PmMode::Mute, &STRAT_QUERY))
+                .await?
+                .pipe(String::from_utf8)?;
+            let cpv = cpv.trim();
+            let mask = format!(">{cpv}");
+            if !cpv.is_empty() && !masked.lines().any(|ln| ln.trim() == mask) {
+                masks.push(mask);
+            }
+        }
+        if masks.is_empty() {
+            return Ok(());
+        }
+        Cmd::with_sudo([
+            "sh",
+            "-c",
+            r#"f=$1; shift; mkdir -p "${f%/*}" && printf '%s\n' "$@" >> "$f""#,
+            "sh",
+            mask_file,
+        ])
+        .kws(masks)
+        .pipe(|cmd| self.run(cmd))
+        .await
+    }
 }
 
 #[async_trait]
@@ -60,11 +119,144 @@ impl Pm for Emerge {
         &self.cfg
     }
 
+    /// Gets the installed version of the given package, or `None` if it is not
+    /// installed, as used by `T`.
+    async fn installed_version(&self, name: &str, flags: &[&str]) -> Result<Option<String>> {
+        Cmd::new(["qlist", "-I", "-F", "%{PVR}"])
+            .kws([name])
+            .flags(flags)
+            .pipe(|cmd| self.try_output_str(cmd))
+            .await
+            .map(|out| {
+                let out = out?;
+                let version = out.lines().next()?.trim();
+                (!version.is_empty()).then(|| version.into())
+            })
+    }
+
+    /// Lists the installed packages as [`Package`] records, aka `Q`.
+    // The names are kept qualified with their categories, but can be looked up
+    // by their bare ones as well.
+    async fn list_installed(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new(["qlist", "-I", "-F", "%{CATEGORY}/%{PN} %{PVR}"])
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| {
+                out.lines()
+                    .filter_map(Package::from_q_line)
+                    .filter(|pkg| {
+                        let bare = pkg.name.rsplit('/').next().unwrap_or(&pkg.name);
+                        kws.is_empty() || kws.iter().any(|&kw| kw == pkg.name || kw == bare)
+                    })
+                    .collect()
+            })
+    }
+
+    /// Lists the packages which have an update available as [`Package`]
+    /// records, aka `Qu`.
+    async fn list_upgrades(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        // E.g. `[ebuild     U  ] app-shells/fish-3.7.1::gentoo [3.7.0::gentoo]`.
+        static UPGRADE: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r"^\[ebuild\s+[^\]]*U[^\]]*\]\s+(\S+?)-(\d\S*?)(?:::(\S+))?\s+\[([^\]:]+)")
+                .expect("ill-formed regex for `emerge -uDNp`")
+        });
+        Cmd::new(["emerge", "-uDNp", "--color=n", "@world"])
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| {
+                out.lines()
+                    .filter_map(|ln| UPGRADE.captures(ln))
+                    .map(|caps| {
+                        let pkg = Package::new(&caps[1])
+                            .version(&caps[4])
+                            .new_version(&caps[2])
+                            .installed(true);
+                        match caps.get(3) {
+                            Some(repo) => pkg.repo(repo.as_str()),
+                            None => pkg,
+                        }
+                    })
+                    .filter(|pkg| {
+                        let bare = pkg.name.rsplit('/').next().unwrap_or(&pkg.name);
+                        kws.is_empty() || kws.iter().any(|&kw| kw == pkg.name || kw == bare)
+                    })
+                    .collect()
+            })
+    }
+
+    /// Lists the explicitly installed packages as [`Package`] records, aka
+    /// `Qe`.
+    // The names are kept qualified with their categories, e.g.
+    // `app-shells/fish`, since they can be ambiguous otherwise.
+    async fn list_explicit(&self, flags: &[&str]) -> Result<Vec<Package>> {
+        let world = fs::read_to_string(WORLD_FILE).unwrap_or_default();
+        let world: Vec<_> = world
+            .lines()
+            // Drops the slot, if any, e.g. `dev-lang/python:3.12`.
+            .filter_map(|ln| ln.trim().split(':').next())
+            .filter(|atom| !atom.is_empty())
+            .collect();
+        Ok(self
+            .list_installed(&[], flags)
+            .await?
+            .into_iter()
+            .filter(|pkg| world.contains(&pkg.name.as_str()))
+            .map(|pkg| pkg.explicit(true))
+            .collect())
+    }
+
+    /// Dd marks packages as non-explicitly installed, aka `--asdeps`.
+    async fn dd(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        Cmd::with_sudo(["emerge", "--deselect"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_ASK))
+            .await
+    }
+
+    /// De marks packages as explicitly installed, aka `--asexplicit`.
+    async fn de(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        Cmd::with_sudo(["emerge", "--noreplace"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_ASK))
+            .await
+    }
+
+    /// F queries the packages from the sync sources which provide FILE.
+    async fn f(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new(["e-file"]).kws(kws).flags(flags)).await
+    }
+
     /// Q generates a list of installed packages.
     async fn q(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
         self.qs(kws, flags).await
     }
 
+    /// Qdt lists orphans, i.e. packages installed as dependencies but no
+    /// longer required by any installed package.
+    // `emerge --depclean` only considers the given atoms, if any.
+    async fn qdt(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        Cmd::new(["emerge", "--depclean", "--pretend"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run(cmd))
+            .await
+    }
+
+    /// Qh lists packages held back from upgrades, aka `Q --held`.
+    async fn qh(&self, _kws: &[&str], _flags: &[&str]) -> Result<()> {
+        fs::read_to_string(Self::mask_file())
+            .unwrap_or_default()
+            .lines()
+            .map(str::trim)
+            .filter(|ln| !(ln.is_empty() || ln.starts_with('#')))
+            .for_each(|ln| println!("{ln}"));
+        Ok(())
+    }
+
     /// Qi displays local package information: name, version, description, etc.
     async fn qi(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
         self.si(kws, flags).await
@@ -153,6 +345,7 @@ impl Pm for Emerge {
 
     /// Su updates outdated packages.
     async fn su(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.mask_ignored().await?;
         Cmd::with_sudo(["emerge", "-uDN"])
             .kws(if kws.is_empty() { &["@world"][..] } else { kws })
             .flags(flags)
@@ -0,0 +1,586 @@
+#![doc = doc_self!()]
+
+use std::{iter, sync::LazyLock};
+
+use async_trait::async_trait;
+use indoc::indoc;
+use itertools::Itertools;
+use tap::prelude::*;
+
+use super::{NoCacheStrategy, Pm, PmHelper, PromptStrategy, Strategy};
+use crate::{
+    config::Config,
+    error::{Error, Failure, Result},
+    exec::Cmd,
+    package::Package,
+};
+
+macro_rules! doc_self {
+    () => {
+        indoc! {"
+            The [Arch Linux package manager](https://wiki.archlinux.org/title/Pacman),
+            and the [AUR helpers](https://wiki.archlinux.org/title/AUR_helpers) with a
+            `pacman`-like interface, eg. [`paru`](https://github.com/Morganamilo/paru)
+            and [`yay`](https://github.com/Jguer/yay).
+
+            As `pacaptr` speaks `pacman` natively, the operations are simply
+            forwarded to `pacman` (or the helper) with the same options.
+            The helpers must never be run with `sudo`, as they elevate their
+            privileges by themselves when needed.
+        "}
+    };
+}
+use doc_self;
+
+#[doc = doc_self!()]
+#[derive(Debug)]
+pub struct Pacman {
+    cfg: Config,
+}
+
+static STRAT_PROMPT: LazyLock<Strategy> = LazyLock::new(|| Strategy {
+    prompt: PromptStrategy::native_no_confirm(["--noconfirm"]),
+    ..Strategy::default()
+});
+
+static STRAT_INSTALL: LazyLock<Strategy> = LazyLock::new(|| Strategy {
+    prompt: PromptStrategy::native_no_confirm(["--noconfirm"]),
+    no_cache: NoCacheStrategy::Scc,
+    ..Strategy::default()
+});
+
+/// Parses the output of `pacman -Ss`, in which each `repo/name version` line
+/// is followed by an indented description.
+fn parse_search(out: &str) -> Vec<Package> {
+    let mut pkgs: Vec<Package> = vec![];
+    for ln in out.lines() {
+        if ln.starts_with(char::is_whitespace) {
+            if let Some(pkg) = pkgs.pop() {
+                pkgs.push(pkg.description(ln.trim()));
+            }
+            continue;
+        }
+        let mut words = ln.split_whitespace();
+        let (Some(id), Some(version)) = (words.next(), words.next()) else {
+            continue;
+        };
+        let pkg = match id.split_once('/') {
+            Some((repo, name)) => Package::new(name).repo(repo),
+            None => Package::new(id),
+        };
+        pkgs.push(
+            pkg.version(version)
+                .installed(words.any(|w| w.starts_with("[installed"))),
+        );
+    }
+    pkgs
+}
+
+impl Pacman {
+    #[must_use]
+    #[allow(missing_docs)]
+    pub const fn new(cfg: Config) -> Self {
+        Self { cfg }
+    }
+
+    /// Returns the command used to invoke [`Pacman`], eg. `pacman`, `paru`.
+    #[must_use]
+    fn cmd(&self) -> &str {
+        self.cfg
+            .default_pm
+            .as_deref()
+            .expect("default package manager should have been assigned before initialization")
+    }
+
+    /// Returns whether [`Pacman`] is invoked as an AUR helper, eg. `paru`.
+    fn is_aur_helper(&self) -> bool {
+        self.cmd() != "pacman"
+    }
+
+    /// Makes a [`Cmd`] for the given privileged operation, e.g. `-S`, run with
+    /// `sudo` unless by an AUR helper.
+    fn sudo_cmd(&self, op: &[&str]) -> Cmd {
+        Cmd::new(iter::once(self.cmd()).chain(op.iter().copied())).sudo(!self.is_aur_helper())
+    }
+
+    /// Makes a [`Cmd`] for the given query operation, e.g. `-Qs`, passing
+    /// `-q` along if only the package names should be printed.
+    fn query_cmd(&self, op: &str) -> Cmd {
+        let cmd = [self.cmd(), op];
+        if self.cfg.names_only {
+            Cmd::new(cmd.into_iter().chain(["-q"]))
+        } else {
+            Cmd::new(cmd)
+        }
+    }
+
+    /// Returns the flags telling `pacman` to hold back the packages and groups
+    /// given by `--ignore` and `--ignoregroup`.
+    fn ignore_flags(&self) -> Vec<String> {
+        [
+            ("--ignore", &self.cfg.ignore),
+            ("--ignoregroup", &self.cfg.ignore_group),
+        ]
+        .into_iter()
+        .filter(|(_, pkgs)| !pkgs.is_empty())
+        .flat_map(|(flag, pkgs)| [flag.to_owned(), pkgs.join(",")])
+        .collect()
+    }
+}
+
+#[async_trait]
+impl Pm for Pacman {
+    /// Gets the name of the package manager.
+    fn name(&self) -> &str {
+        self.cmd()
+    }
+
+    fn cfg(&self) -> &Config {
+        &self.cfg
+    }
+
+    fn native_names_only(&self) -> bool {
+        true
+    }
+
+    fn failures(&self) -> &'static [(Failure, &'static str)] {
+        &[
+            (
+                Failure::PermissionDenied,
+                r"you cannot perform this operation unless you are root",
+            ),
+            (Failure::LockHeld, r"unable to lock database"),
+            (Failure::PackageNotFound, r"^error: target not found"),
+            (Failure::Network, r"failed retrieving file"),
+            (Failure::DiskFull, r"not enough free disk space"),
+            (
+                Failure::PackageConflict,
+                r"conflicting (files|dependencies|packages)|are in conflict|exists in filesystem",
+            ),
+        ]
+    }
+
+    /// Lists the installed packages as [`Package`] records, aka `Q`.
+    async fn list_installed(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new([self.cmd(), "-Q"])
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| {
+                out.lines()
+                    .filter_map(Package::from_q_line)
+                    .filter(|pkg| kws.is_empty() || kws.contains(&pkg.name.as_str()))
+                    .collect()
+            })
+    }
+
+    /// Lists the packages which have an update available as [`Package`]
+    /// records, aka `Qu`.
+    async fn list_upgrades(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        // `pacman -Qu` exits with 1 if everything is up to date.
+        Cmd::new([self.cmd(), "-Qu"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.try_output_str(cmd))
+            .await
+            .map(|out| {
+                out.unwrap_or_default()
+                    .lines()
+                    .filter_map(|ln| {
+                        // E.g. `bash 5.2.026-2 -> 5.2.032-1 [ignored]`.
+                        let mut words = ln.split_whitespace();
+                        let (name, version) = (words.next()?, words.next()?);
+                        words.next().filter(|&arrow| arrow == "->")?;
+                        Some(
+                            Package::new(name)
+                                .version(version)
+                                .new_version(words.next()?)
+                                .installed(true),
+                        )
+                    })
+                    .collect()
+            })
+    }
+
+    /// Searches the sync sources for packages matching all the given
+    /// keywords, aka `Ss`.
+    async fn search(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        // `pacman -Ss` exits with 1 if nothing is found.
+        Cmd::new([self.cmd(), "-Ss"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.try_output_str(cmd))
+            .await
+            .map(|out| out.as_deref().map(parse_search).unwrap_or_default())
+    }
+
+    /// Lists the explicitly installed packages as [`Package`] records, aka
+    /// `Qe`.
+    async fn list_explicit(&self, flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new([self.cmd(), "-Qe"])
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| {
+                out.lines()
+                    .filter_map(Package::from_q_line)
+                    .map(|pkg| pkg.explicit(true))
+                    .collect()
+            })
+    }
+
+    /// Gets the installed version of the given package, or `None` if it is not
+    /// installed, as used by `sync-manifest`.
+    async fn installed_version(&self, name: &str, flags: &[&str]) -> Result<Option<String>> {
+        Cmd::new([self.cmd(), "-Q"])
+            .kws([name])
+            .flags(flags)
+            .pipe(|cmd| self.try_output_str(cmd))
+            .await
+            .map(|out| out?.lines().find_map(Package::from_q_line)?.version)
+    }
+
+    /// Dd marks packages as non-explicitly installed, aka `--asdeps`.
+    async fn dd(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.sudo_cmd(&["-D", "--asdeps"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run(cmd))
+            .await
+    }
+
+    /// De marks packages as explicitly installed, aka `--asexplicit`.
+    async fn de(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.sudo_cmd(&["-D", "--asexplicit"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run(cmd))
+            .await
+    }
+
+    /// F queries the packages from the sync sources which provide FILE.
+    async fn f(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new([self.cmd(), "-F"]).kws(kws).flags(flags))
+            .await
+    }
+
+    /// Fl displays files provided by remote package.
+    async fn fl(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new([self.cmd(), "-Fl"]).kws(kws).flags(flags))
+            .await
+    }
+
+    /// Fx queries the packages from the sync sources which provide a file
+    /// matching the regex.
+    async fn fx(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new([self.cmd(), "-Fx"]).kws(kws).flags(flags))
+            .await
+    }
+
+    /// Fy refreshes the local files database.
+    async fn fy(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.sudo_cmd(&["-Fy"]).flags(flags)).await?;
+        if !kws.is_empty() {
+            self.f(kws, flags).await?;
+        }
+        Ok(())
+    }
+
+    /// Q generates a list of installed packages.
+    async fn q(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Q").kws(kws).flags(flags)).await
+    }
+
+    /// Qc shows the changelog of a package.
+    async fn qc(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Qc").kws(kws).flags(flags)).await
+    }
+
+    /// Qd lists packages installed as dependencies (not explicitly).
+    async fn qd(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Qd").kws(kws).flags(flags)).await
+    }
+
+    /// Qdt lists orphans, i.e. packages installed as dependencies but no
+    /// longer required by any installed package.
+    async fn qdt(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Qdt").kws(kws).flags(flags)).await
+    }
+
+    /// Qe lists packages installed explicitly (not as dependencies).
+    async fn qe(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Qe").kws(kws).flags(flags)).await
+    }
+
+    /// Qh lists packages held back from upgrades, aka `Q --held`.
+    // `-Su` also holds back the packages given by `--ignore` and the `ignore`
+    // config item.
+    async fn qh(&self, _kws: &[&str], flags: &[&str]) -> Result<()> {
+        let out = self
+            .check_output_str(Cmd::new(["pacman-conf", "IgnorePkg"]).flags(flags))
+            .await?;
+        out.lines()
+            .chain(self.cfg.ignore.iter().map(String::as_str))
+            .unique()
+            .for_each(|pkg| println!("{pkg}"));
+        Ok(())
+    }
+
+    /// Qi displays local package information: name, version, description, etc.
+    async fn qi(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Qi").kws(kws).flags(flags)).await
+    }
+
+    /// Qii displays local packages which require X to be installed, aka local
+    /// reverse dependencies.
+    async fn qii(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Qii").kws(kws).flags(flags)).await
+    }
+
+    /// Qk verifies one or more packages.
+    async fn qk(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Qk").kws(kws).flags(flags)).await
+    }
+
+    /// Ql displays files provided by local package.
+    async fn ql(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Ql").kws(kws).flags(flags)).await
+    }
+
+    /// Qm lists packages that are installed but are not available in any
+    /// installation source (anymore).
+    // These are usually the packages installed from the AUR.
+    async fn qm(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Qm").kws(kws).flags(flags)).await
+    }
+
+    /// Qo queries the package which provides FILE.
+    async fn qo(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Qo").kws(kws).flags(flags)).await
+    }
+
+    /// Qp queries a package supplied through a file supplied on the command
+    /// line rather than an entry in the package management database.
+    async fn qp(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Qp").kws(kws).flags(flags)).await
+    }
+
+    /// Qs searches locally installed package for names or descriptions.
+    async fn qs(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Qs").kws(kws).flags(flags)).await
+    }
+
+    /// Qt lists packages not required by any installed package.
+    async fn qt(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Qt").kws(kws).flags(flags)).await
+    }
+
+    /// Qu lists packages which have an update available.
+    async fn qu(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Qu").kws(kws).flags(flags)).await
+    }
+
+    /// R removes a single package, leaving all of its dependencies installed.
+    async fn r(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.sudo_cmd(&["-R"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_PROMPT))
+            .await
+    }
+
+    /// Rn removes a package and skips the generation of configuration backup
+    /// files.
+    async fn rn(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.sudo_cmd(&["-Rn"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_PROMPT))
+            .await
+    }
+
+    /// Rns removes a package and its dependencies which are not required by any
+    /// other installed package, and skips the generation of configuration
+    /// backup files.
+    async fn rns(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.sudo_cmd(&["-Rns"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_PROMPT))
+            .await
+    }
+
+    /// Rs removes a package and its dependencies which are not required by any
+    /// other installed package, and not explicitly installed by the user.
+    async fn rs(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.sudo_cmd(&["-Rs"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_PROMPT))
+            .await
+    }
+
+    /// Rss removes a package and its dependencies which are not required by any
+    /// other installed package.
+    async fn rss(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.sudo_cmd(&["-Rss"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_PROMPT))
+            .await
+    }
+
+    /// S installs one or more packages by name.
+    async fn s(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.sudo_cmd(if self.cfg.needed {
+            &["-S", "--needed"]
+        } else {
+            &["-S"]
+        })
+        .kws(kws)
+        .flags(flags)
+        .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_INSTALL))
+        .await
+    }
+
+    /// Sas searches for package(s) in the AUR only, aka `Ssa`.
+    async fn sas(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        if !self.is_aur_helper() {
+            return Err(Error::OperationUnimplementedError {
+                op: "sas".into(),
+                pm: self.name().into(),
+            });
+        }
+        self.run(self.query_cmd("-Ssa").kws(kws).flags(flags)).await
+    }
+
+    /// Sau updates outdated packages from the AUR, aka `Sua`.
+    async fn sau(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        if !self.is_aur_helper() {
+            return Err(Error::OperationUnimplementedError {
+                op: "sau".into(),
+                pm: self.name().into(),
+            });
+        }
+        self.sudo_cmd(&["-Sua"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_INSTALL))
+            .await
+    }
+
+    /// Sc removes all the cached packages that are not currently installed, and
+    /// the unused sync database.
+    async fn sc(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.sudo_cmd(&["-Sc"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_PROMPT))
+            .await
+    }
+
+    /// Scc removes all files from the cache.
+    async fn scc(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.sudo_cmd(&["-Scc"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_PROMPT))
+            .await
+    }
+
+    /// Sccc performs a deeper cleaning of the cache than `Scc` (if applicable).
+    // `pacman` has no deeper cleaning than `-Scc`, and neither do the AUR
+    // helpers.
+    async fn sccc(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.sudo_cmd(&["-Scc"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_PROMPT))
+            .await
+    }
+
+    /// Sg lists all packages belonging to the GROUP.
+    async fn sg(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Sg").kws(kws).flags(flags)).await
+    }
+
+    /// Si displays remote package information: name, version, description, etc.
+    async fn si(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Si").kws(kws).flags(flags)).await
+    }
+
+    /// Sii displays packages which require X to be installed, aka reverse
+    /// dependencies.
+    async fn sii(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Sii").kws(kws).flags(flags)).await
+    }
+
+    /// Sl displays a list of all packages in all installation sources that are
+    /// handled by the package management.
+    async fn sl(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Sl").kws(kws).flags(flags)).await
+    }
+
+    /// Ss searches for package(s) by searching the expression in name,
+    /// description, short description.
+    async fn ss(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(self.query_cmd("-Ss").kws(kws).flags(flags)).await
+    }
+
+    /// Su updates outdated packages.
+    async fn su(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.sudo_cmd(&["-Su"])
+            .kws(kws)
+            .flags(flags)
+            .tap_mut(|cmd| cmd.flags.extend(self.ignore_flags()))
+            .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_INSTALL))
+            .await
+    }
+
+    /// Suy refreshes the local package database, then updates outdated
+    /// packages.
+    async fn suy(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.sudo_cmd(&["-Suy"])
+            .kws(kws)
+            .flags(flags)
+            .tap_mut(|cmd| cmd.flags.extend(self.ignore_flags()))
+            .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_INSTALL))
+            .await
+    }
+
+    /// Sw retrieves all packages from the server, but does not install/upgrade
+    /// anything.
+    async fn sw(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.sudo_cmd(&["-Sw"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_PROMPT))
+            .await
+    }
+
+    /// Sy refreshes the local package database.
+    async fn sy(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.sudo_cmd(&["-Sy"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run(cmd))
+            .await
+    }
+
+    /// T prints the dependencies which are not satisfied by the installed
+    /// packages.
+    async fn t(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new([self.cmd(), "-T"]).kws(kws).flags(flags))
+            .await
+    }
+
+    /// U upgrades or adds package(s) to the system and installs the required
+    /// dependencies from sync repositories.
+    async fn u(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.sudo_cmd(&["-U"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_INSTALL))
+            .await
+    }
+}
@@ -4,6 +4,8 @@ use std::sync::LazyLock;
 
 use async_trait::async_trait;
 use indoc::indoc;
+use itertools::Itertools;
+use serde::Deserialize;
 use tap::prelude::*;
 
 use super::{Pm, PmHelper, PromptStrategy, Strategy};
@@ -11,6 +13,7 @@ use crate::{
     config::Config,
     error::{Error, Result},
     exec::Cmd,
+    package::Package,
 };
 
 macro_rules! doc_self {
@@ -38,6 +41,58 @@ static STRAT_UNINSTALL: LazyLock<Strategy> = LazyLock::new(|| Strategy {
     ..Strategy::default()
 });
 
+/// An item in the output of `pip list --format json`.
+#[derive(Debug, Deserialize)]
+struct ListItemJson {
+    name: String,
+    version: String,
+    latest_version: Option<String>,
+}
+
+impl From<ListItemJson> for Package {
+    fn from(item: ListItemJson) -> Self {
+        Self {
+            new_version: item.latest_version,
+            ..Self::new(item.name).version(item.version)
+        }
+    }
+}
+
+/// Parses the output of `pip show`, in which the packages are separated by
+/// `---` lines.
+fn parse_pip_show(out: &str) -> Vec<Package> {
+    out.split("\n---")
+        .filter_map(|section| {
+            let field = |key: &str| {
+                section.lines().find_map(|ln| {
+                    let val = ln.strip_prefix(key)?.strip_prefix(':')?;
+                    Some(val.trim().to_owned())
+                })
+            };
+            Some(Package {
+                version: field("Version"),
+                description: field("Summary"),
+                installed: true,
+                ..Package::new(field("Name")?)
+            })
+        })
+        .collect()
+}
+
+/// Parses a line in the requirement syntax of `pip freeze`, e.g.
+/// `requests==2.31.0` or `foo @ file:///tmp/foo`.
+fn parse_requirement(ln: &str) -> Option<Package> {
+    let ln = ln.trim();
+    if ln.is_empty() || ln.starts_with(['#', '-']) {
+        return None;
+    }
+    if let Some((name, version)) = ln.split_once("==") {
+        return Some(Package::new(name.trim()).version(version.trim()));
+    }
+    let name = ln.split_once(" @ ").map_or(ln, |(name, _)| name);
+    Some(Package::new(name.trim()))
+}
+
 impl Pip {
     #[must_use]
     #[allow(missing_docs)]
@@ -45,6 +100,17 @@ impl Pip {
         Self { cfg }
     }
 
+    /// Runs `pip list --format json` with the given arguments.
+    async fn list_json(&self, args: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        let out = Cmd::new([self.cmd(), "list", "--format", "json"])
+            .kws(args)
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await?;
+        let items: Vec<ListItemJson> = serde_json::from_str(&out)?;
+        Ok(items.into_iter().map_into().collect())
+    }
+
     /// Returns the command used to invoke [`Pip`], eg. `pip`, `pip3`.
     #[must_use]
     fn cmd(&self) -> &str {
@@ -66,13 +132,78 @@ impl Pm for Pip {
         &self.cfg
     }
 
+    /// Lists the installed packages as [`Package`] records, aka `Q`.
+    async fn list_installed(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Ok(self
+            .list_json(&[], flags)
+            .await?
+            .into_iter()
+            .filter(|pkg| kws.is_empty() || kws.iter().any(|kw| pkg.name.eq_ignore_ascii_case(kw)))
+            .map(|pkg| pkg.installed(true))
+            .collect())
+    }
+
+    /// Gets the [`Package`] records of the given installed packages, aka `Qi`.
+    async fn info_installed(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new([self.cmd(), "show"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| parse_pip_show(&out))
+    }
+
+    /// Lists the explicitly installed packages as [`Package`] records, aka
+    /// `Qe`.
+    // `pip` doesn't keep track of the installation reason, so we take the
+    // packages not required by any other one instead.
+    async fn list_explicit(&self, flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new([self.cmd(), "list", "--not-required", "--format", "freeze"])
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| {
+                out.lines()
+                    .filter_map(parse_requirement)
+                    .map(|pkg| pkg.installed(true).explicit(true))
+                    .collect()
+            })
+    }
+
+    /// Lists the packages which have an update available as [`Package`]
+    /// records, aka `Qu`.
+    async fn list_upgrades(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Ok(self
+            .list_json(&["--outdated"], flags)
+            .await?
+            .into_iter()
+            .filter(|pkg| kws.is_empty() || kws.iter().any(|kw| pkg.name.eq_ignore_ascii_case(kw)))
+            .collect())
+    }
+
+    /// Gets the installed version of the given package, or `None` if it is not
+    /// installed, as used by `T`.
+    async fn installed_version(&self, name: &str, flags: &[&str]) -> Result<Option<String>> {
+        Cmd::new([self.cmd(), "show"])
+            .kws([name])
+            .flags(flags)
+            .pipe(|cmd| self.try_output_str(cmd))
+            .await
+            .map(|out| {
+                parse_pip_show(&out?)
+                    .into_iter()
+                    .find_map(|pkg| pkg.version)
+            })
+    }
+
     /// Q generates a list of installed packages.
     async fn q(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
-        if kws.is_empty() {
-            self.run(Cmd::new([self.cmd(), "list"]).flags(flags)).await
-        } else {
-            self.qs(kws, flags).await
-        }
+        self.print_installed(kws, flags).await
+    }
+
+    /// Qe lists packages installed explicitly (not as dependencies).
+    async fn qe(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.print_explicit(kws, flags).await
     }
 
     /// Qi displays local package information: name, version, description, etc.
@@ -86,12 +217,22 @@ impl Pm for Pip {
     // when including multiple search terms, only packages with descriptions
     // matching ALL of those terms are returned.
     async fn qs(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
-        self.search_regex(Cmd::new([self.cmd(), "list"]).flags(flags), kws)
+        self.print_installed_search(kws, flags).await
+    }
+
+    /// Qt lists packages not required by any installed package.
+    async fn qt(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        Cmd::new([self.cmd(), "list", "--not-required"])
+            .flags(flags)
+            .pipe(|cmd| self.search_names(cmd, kws, |ln| ln.split_whitespace().next()))
             .await
     }
 
     /// Qu lists packages which have an update available.
     async fn qu(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        if self.cfg.names_only {
+            return self.print_upgrades(kws, flags).await;
+        }
         Cmd::new([self.cmd(), "list", "--outdated"])
             .kws(kws)
             .flags(flags)
@@ -148,4 +289,10 @@ impl Pm for Pip {
             .pipe(|cmd| self.run(cmd))
             .await
     }
+
+    /// T prints the dependencies which are not satisfied by the installed
+    /// packages.
+    async fn t(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.deptest(kws, flags).await
+    }
 }
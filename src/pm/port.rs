@@ -7,7 +7,7 @@ use indoc::indoc;
 use tap::prelude::*;
 
 use super::{NoCacheStrategy, Pm, PmHelper, PromptStrategy, Strategy};
-use crate::{config::Config, error::Result, exec::Cmd};
+use crate::{config::Config, error::Result, exec::Cmd, package::Package};
 
 macro_rules! doc_self {
     () => {
@@ -41,6 +41,28 @@ impl Port {
     pub const fn new(cfg: Config) -> Self {
         Self { cfg }
     }
+
+    /// Runs `port installed` with the given pseudo-ports (e.g. `active`) and
+    /// returns the [`Package`] records listed.
+    async fn installed(&self, pseudo: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new(["port", "-q", "installed"])
+            .kws(pseudo)
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| {
+                out.lines()
+                    .filter_map(|ln| {
+                        // E.g. `  fish @3.7.1_0+universal (active)`.
+                        let mut words = ln.split_whitespace();
+                        let name = words.next()?;
+                        let version = words.next()?.strip_prefix('@')?;
+                        let version = version.split('+').next().unwrap_or(version);
+                        Some(Package::new(name).version(version).installed(true))
+                    })
+                    .collect()
+            })
+    }
 }
 
 #[async_trait]
@@ -54,6 +76,53 @@ impl Pm for Port {
         &self.cfg
     }
 
+    /// Lists the installed packages as [`Package`] records, aka `Q`.
+    async fn list_installed(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Ok(self
+            .installed(&["active"], flags)
+            .await?
+            .into_iter()
+            .filter(|pkg| kws.is_empty() || kws.contains(&pkg.name.as_str()))
+            .collect())
+    }
+
+    /// Lists the packages which have an update available as [`Package`]
+    /// records, aka `Qu`.
+    async fn list_upgrades(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new(["port", "-q", "outdated"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| {
+                out.lines()
+                    .filter_map(|ln| {
+                        // E.g. `fish  3.7.0_0 < 3.7.1_0`.
+                        let mut words = ln.split_whitespace();
+                        let (name, version) = (words.next()?, words.next()?);
+                        words.next().filter(|&lt| lt == "<")?;
+                        Some(
+                            Package::new(name)
+                                .version(version)
+                                .new_version(words.next()?)
+                                .installed(true),
+                        )
+                    })
+                    .collect()
+            })
+    }
+
+    /// Lists the explicitly installed packages as [`Package`] records, aka
+    /// `Qe`.
+    async fn list_explicit(&self, flags: &[&str]) -> Result<Vec<Package>> {
+        Ok(self
+            .installed(&["requested", "and", "active"], flags)
+            .await?
+            .into_iter()
+            .map(|pkg| pkg.explicit(true))
+            .collect())
+    }
+
     /// Q generates a list of installed packages.
     async fn q(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
         self.run(Cmd::new(["port", "installed"]).kws(kws).flags(flags))
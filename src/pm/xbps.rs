@@ -12,6 +12,8 @@ use crate::{
     config::Config,
     error::{Error, Result},
     exec::{Cmd, StatusCode},
+    lock::Lock,
+    package::Package,
     print::println_err,
 };
 
@@ -37,6 +39,29 @@ static STRAT_PROMPT: LazyLock<Strategy> = LazyLock::new(|| Strategy {
     ..Strategy::default()
 });
 
+/// Parses the output of `xbps-query -l` and `xbps-query -Rs`, in which each
+/// line is made of a state (e.g. `ii` or `[*]` if installed), a
+/// `name-version` and a description.
+fn parse_xbps_list(out: &str) -> Vec<Package> {
+    out.lines()
+        .filter_map(|ln| {
+            let (state, rest) = ln.trim().split_once(char::is_whitespace)?;
+            let rest = rest.trim_start();
+            let (pkgver, desc) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            let (name, version) = pkgver.rsplit_once('-')?;
+            let pkg = Package::new(name)
+                .version(version)
+                .installed(matches!(state, "ii" | "[*]"));
+            let desc = desc.trim();
+            Some(if desc.is_empty() {
+                pkg
+            } else {
+                pkg.description(desc)
+            })
+        })
+        .collect()
+}
+
 impl Xbps {
     #[must_use]
     #[allow(missing_docs)]
@@ -56,6 +81,136 @@ impl Pm for Xbps {
         &self.cfg
     }
 
+    fn locks(&self) -> &'static [Lock] {
+        &[Lock::File("/var/db/xbps/lock")]
+    }
+
+    /// Lists the installed packages as [`Package`] records, aka `Q`.
+    async fn list_installed(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new(["xbps-query", "-l"])
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| {
+                parse_xbps_list(&out)
+                    .into_iter()
+                    .filter(|pkg| kws.is_empty() || kws.contains(&pkg.name.as_str()))
+                    .collect()
+            })
+    }
+
+    /// Lists the packages which have an update available as [`Package`]
+    /// records, aka `Qu`.
+    async fn list_upgrades(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        // Each line of the transaction is made of the new `name-version`, the
+        // action, the architecture, the repository and the sizes.
+        let out = Cmd::new(["xbps-install", "-Mun"])
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await?;
+        let installed = self.list_installed(&[], flags).await?;
+        Ok(out
+            .lines()
+            .filter_map(|ln| {
+                let mut words = ln.split_whitespace();
+                let pkgver = words.next()?;
+                words.next().filter(|&action| action == "update")?;
+                let (name, new_version) = pkgver.rsplit_once('-')?;
+                let pkg = installed.iter().find(|pkg| pkg.name == name)?;
+                Some(pkg.clone().new_version(new_version))
+            })
+            .filter(|pkg| kws.is_empty() || kws.contains(&pkg.name.as_str()))
+            .collect())
+    }
+
+    /// Searches the sync sources for packages matching all the given
+    /// keywords, aka `Ss`.
+    async fn search(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new(["xbps-query", "-Rs"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| parse_xbps_list(&out))
+    }
+
+    /// Gets the installed version of the given package, or `None` if it is not
+    /// installed, as used by `T`.
+    async fn installed_version(&self, name: &str, flags: &[&str]) -> Result<Option<String>> {
+        Cmd::new(["xbps-query", "-p", "pkgver"])
+            .kws([name])
+            .flags(flags)
+            .pipe(|cmd| self.try_output_str(cmd))
+            .await
+            .map(|out| {
+                let out = out?;
+                let (_, version) = out.trim().rsplit_once('-')?;
+                Some(version.into())
+            })
+    }
+
+    /// Lists the explicitly installed packages as [`Package`] records, aka
+    /// `Qe`.
+    async fn list_explicit(&self, flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new(["xbps-query", "-m"])
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| {
+                out.lines()
+                    .filter_map(|pkgver| {
+                        let (name, version) = pkgver.trim().rsplit_once('-')?;
+                        Some(
+                            Package::new(name)
+                                .version(version)
+                                .installed(true)
+                                .explicit(true),
+                        )
+                    })
+                    .collect()
+            })
+    }
+
+    /// Dd marks packages as non-explicitly installed, aka `--asdeps`.
+    async fn dd(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        Cmd::with_sudo(["xbps-pkgdb", "-m", "auto"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run(cmd))
+            .await
+    }
+
+    /// De marks packages as explicitly installed, aka `--asexplicit`.
+    async fn de(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        Cmd::with_sudo(["xbps-pkgdb", "-m", "manual"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run(cmd))
+            .await
+    }
+
+    /// F queries the packages from the sync sources which provide FILE.
+    async fn f(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new(["xbps-query", "-Ro"]).kws(kws).flags(flags))
+            .await
+    }
+
+    /// Fl displays files provided by remote package.
+    async fn fl(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new(["xbps-query", "-Rf"]).kws(kws).flags(flags))
+            .await
+    }
+
+    /// Fy refreshes the local files database.
+    async fn fy(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::with_sudo(["xbps-install", "-S"]).flags(flags))
+            .await?;
+        if !kws.is_empty() {
+            self.f(kws, flags).await?;
+        }
+        Ok(())
+    }
+
     /// Q generates a list of installed packages.
     async fn q(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
         if kws.is_empty() {
@@ -96,6 +251,16 @@ impl Pm for Xbps {
         })
     }
 
+    /// Qdt lists orphans, i.e. packages installed as dependencies but no
+    /// longer required by any installed package.
+    async fn qdt(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        // Each line is a `pkgver`, e.g. `curl-8.5.0_1`.
+        Cmd::new(["xbps-query", "-O"])
+            .flags(flags)
+            .pipe(|cmd| self.search_names(cmd, kws, |ln| ln.rsplit_once('-').map(|(name, _)| name)))
+            .await
+    }
+
     /// Qe lists packages installed explicitly (not as dependencies).
     async fn qe(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
         if kws.is_empty() {
@@ -150,6 +315,11 @@ impl Pm for Xbps {
         })?
     }
 
+    /// Qh lists packages held back from upgrades, aka `Q --held`.
+    async fn qh(&self, _kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new(["xbps-query", "-H"]).flags(flags)).await
+    }
+
     /// Qi displays local package information: name, version, description, etc.
     async fn qi(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
         self.run(Cmd::new(["xbps-query", "-S"]).kws(kws).flags(flags))
@@ -236,6 +406,8 @@ impl Pm for Xbps {
     /// Suy refreshes the local package database, then updates outdated
     /// packages.
     async fn suy(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.hold_ignored(Cmd::with_sudo(["xbps-pkgdb", "-m", "hold"]))
+            .await?;
         Cmd::with_sudo(["xbps-install", "-Su"])
             .kws(kws)
             .flags(flags)
@@ -261,10 +433,18 @@ impl Pm for Xbps {
 
     /// Su updates outdated packages.
     async fn su(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.hold_ignored(Cmd::with_sudo(["xbps-pkgdb", "-m", "hold"]))
+            .await?;
         Cmd::with_sudo(["xbps-install", "-u"])
             .kws(kws)
             .flags(flags)
             .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_PROMPT))
             .await
     }
+
+    /// T prints the dependencies which are not satisfied by the installed
+    /// packages.
+    async fn t(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.deptest(kws, flags).await
+    }
 }
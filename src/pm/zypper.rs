@@ -1,16 +1,19 @@
 #![doc = doc_self!()]
 
-use std::sync::LazyLock;
+use std::{fs, sync::LazyLock};
 
 use async_trait::async_trait;
 use indoc::indoc;
+use itertools::{Itertools, chain};
 use tap::prelude::*;
 
 use super::{DryRunStrategy, NoCacheStrategy, Pm, PmHelper, PmMode, PromptStrategy, Strategy};
 use crate::{
     config::Config,
-    error::Result,
+    error::{Error, Failure, Result},
     exec::{self, Cmd},
+    lock::Lock,
+    package::Package,
 };
 
 macro_rules! doc_self {
@@ -28,11 +31,24 @@ pub struct Zypper {
     cfg: Config,
 }
 
+/// The file listing the packages installed as dependencies.
+const AUTO_INSTALLED_FILE: &str = "/var/lib/zypp/AutoInstalled";
+
 static STRAT_CHECK_DRY: LazyLock<Strategy> = LazyLock::new(|| Strategy {
     dry_run: DryRunStrategy::with_flags(["--dry-run"]),
     ..Strategy::default()
 });
 
+static STRAT_QUERY: LazyLock<Strategy> = LazyLock::new(|| Strategy {
+    dry_run: DryRunStrategy::WithFlags(vec![]),
+    ..Strategy::default()
+});
+
+static STRAT_PROMPT_CUSTOM: LazyLock<Strategy> = LazyLock::new(|| Strategy {
+    prompt: PromptStrategy::CustomPrompt,
+    ..Strategy::default()
+});
+
 static STRAT_PROMPT: LazyLock<Strategy> = LazyLock::new(|| Strategy {
     prompt: PromptStrategy::native_no_confirm(["-y"]),
     dry_run: DryRunStrategy::with_flags(["--dry-run"]),
@@ -45,6 +61,38 @@ static STRAT_INSTALL: LazyLock<Strategy> = LazyLock::new(|| Strategy {
     dry_run: DryRunStrategy::with_flags(["--dry-run"]),
 });
 
+/// Parses the table printed by `zypper list-updates`, whose columns are found
+/// by their headers, e.g. `S | Repository | Name | Current Version | ...`.
+fn parse_list_updates(out: &str) -> Vec<Package> {
+    let mut lines = out
+        .lines()
+        .map(|ln| ln.split('|').map(str::trim).collect_vec());
+    let Some(header) = lines.find(|cols| cols.contains(&"Name")) else {
+        return vec![];
+    };
+    let col = |name: &str| header.iter().position(|&it| it == name);
+    let (Some(name), Some(new_version)) = (col("Name"), col("Available Version")) else {
+        return vec![];
+    };
+    let (version, repo) = (col("Current Version"), col("Repository"));
+    lines
+        .filter(|cols| cols.len() == header.len())
+        .map(|cols| {
+            let pkg = Package::new(cols[name])
+                .new_version(cols[new_version])
+                .installed(true);
+            let pkg = match version {
+                Some(i) => pkg.version(cols[i]),
+                None => pkg,
+            };
+            match repo {
+                Some(i) => pkg.repo(cols[i]),
+                None => pkg,
+            }
+        })
+        .collect()
+}
+
 impl Zypper {
     #[must_use]
     #[allow(missing_docs)]
@@ -69,6 +117,202 @@ impl Pm for Zypper {
         &self.cfg
     }
 
+    fn locks(&self) -> &'static [Lock] {
+        &[
+            Lock::PidFile("/var/run/zypp.pid"),
+            Lock::File("/var/lib/rpm/.rpm.lock"),
+        ]
+    }
+
+    fn failures(&self) -> &'static [(Failure, &'static str)] {
+        &[
+            (Failure::PermissionDenied, r"root privileges are required"),
+            (
+                Failure::LockHeld,
+                r"system management is locked by the application with pid \d+",
+            ),
+            (
+                Failure::PackageNotFound,
+                r"no provider of '.+' found|package '.+' not found|not found in package names",
+            ),
+            (
+                Failure::Network,
+                r"download \(curl\) error|timeout exceeded when accessing",
+            ),
+            (Failure::DiskFull, r"not enough (free )?space|disk space"),
+            (Failure::PackageConflict, r"conflicts with|file conflicts"),
+        ]
+    }
+
+    /// Gets the installed version of the given package, or `None` if it is not
+    /// installed, as used by `T`.
+    async fn installed_version(&self, name: &str, flags: &[&str]) -> Result<Option<String>> {
+        Cmd::new([
+            "rpm",
+            "-q",
+            "--qf",
+            "%|EPOCH?{%{EPOCH}:}:{}|%{VERSION}-%{RELEASE}\\n",
+        ])
+        .kws([name])
+        .flags(flags)
+        .pipe(|cmd| self.try_output_str(cmd))
+        .await
+        .map(|out| out?.lines().last().map(Into::into))
+    }
+
+    /// Lists the installed packages as [`Package`] records, aka `Q`.
+    async fn list_installed(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new([
+            "rpm",
+            "-qa",
+            "--qf",
+            "%{NAME} %|EPOCH?{%{EPOCH}:}:{}|%{VERSION}-%{RELEASE}\\n",
+        ])
+        .flags(flags)
+        .pipe(|cmd| self.check_output_str(cmd))
+        .await
+        .map(|out| {
+            out.lines()
+                .filter_map(Package::from_q_line)
+                // `gpg-pubkey` entries are the imported keys, not packages.
+                .filter(|pkg| pkg.name != "gpg-pubkey")
+                .filter(|pkg| kws.is_empty() || kws.contains(&pkg.name.as_str()))
+                .sorted_by(|p, q| p.name.cmp(&q.name))
+                .collect()
+        })
+    }
+
+    /// Lists the packages which have an update available as [`Package`]
+    /// records, aka `Qu`.
+    async fn list_upgrades(&self, kws: &[&str], flags: &[&str]) -> Result<Vec<Package>> {
+        Cmd::new(["zypper", "--no-refresh", "--quiet", "list-updates"])
+            .flags(flags)
+            .pipe(|cmd| self.check_output_str(cmd))
+            .await
+            .map(|out| {
+                parse_list_updates(&out)
+                    .into_iter()
+                    .filter(|pkg| kws.is_empty() || kws.contains(&pkg.name.as_str()))
+                    .collect()
+            })
+    }
+
+    /// Lists the explicitly installed packages as [`Package`] records, aka
+    /// `Qe`.
+    // Every installed package counts as explicit unless `zypper` has recorded
+    // it in its `AutoInstalled` file, i.e. pulled it in as a dependency.
+    async fn list_explicit(&self, flags: &[&str]) -> Result<Vec<Package>> {
+        let auto = fs::read_to_string(AUTO_INSTALLED_FILE).unwrap_or_default();
+        let auto: Vec<_> = auto
+            .lines()
+            .map(str::trim)
+            .filter(|ln| !ln.is_empty() && !ln.starts_with('#'))
+            .collect();
+        Ok(self
+            .list_installed(&[], flags)
+            .await?
+            .into_iter()
+            .filter(|pkg| !auto.contains(&pkg.name.as_str()))
+            .map(|pkg| pkg.explicit(true))
+            .collect())
+    }
+
+    /// Reverts the last `n` transactions with `snapper`, as used by `--undo`.
+    // `zypper` has no history to undo by itself, but with the `snapper`
+    // integration each transaction is wrapped in a pair of pre/post snapshots,
+    // whose changes can then be undone, newest first.
+    async fn undo(&self, n: usize, flags: &[&str]) -> Result<()> {
+        if !exec::is_exe("snapper", "") {
+            return Err(Error::OperationUnimplementedError {
+                op: "undo".into(),
+                pm: self.name().into(),
+            });
+        }
+        let out = Cmd::with_sudo([
+            "snapper",
+            "--csvout",
+            "list",
+            "--type",
+            "pre-post",
+            "--columns",
+            "pre-number,post-number,description",
+        ])
+        .pipe(|cmd| self.check_output(cmd, PmMode::Mute, &STRAT_QUERY))
+        .await?
+        .pipe(String::from_utf8)?;
+        let pairs = out
+            .lines()
+            .filter_map(|ln| {
+                let (pre, post, desc) = ln.splitn(3, ',').collect_tuple()?;
+                desc.starts_with("zypp(").then(|| format!("{pre}..{post}"))
+            })
+            .collect_vec();
+        for pair in pairs.iter().rev().take(n) {
+            Cmd::with_sudo(["snapper", "undochange"])
+                .kws([pair])
+                .flags(flags)
+                .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_PROMPT_CUSTOM))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Dd marks packages as non-explicitly installed, aka `--asdeps`.
+    // `zypper` has no command for this, but `libzypp` keeps the packages
+    // installed as dependencies in the `AutoInstalled` file, so we add the
+    // corresponding entries to it.
+    async fn dd(&self, kws: &[&str], _flags: &[&str]) -> Result<()> {
+        // `sed` runs no command at all on an empty file, so the lines are
+        // appended by the shell instead, after dropping any existing ones.
+        let names = kws.iter().map(|kw| exec::sed_escape(kw)).join("|");
+        Cmd::with_sudo([
+            "sh",
+            "-c",
+            r#"f=$1 d=$2; shift 2; touch "$f" && sed -i -E "$d" "$f" && printf '%s\n' "$@" >> "$f""#,
+            "sh",
+            AUTO_INSTALLED_FILE,
+            &format!("/^({names})$/d"),
+        ])
+        .kws(kws)
+        .pipe(|cmd| self.run(cmd))
+        .await
+    }
+
+    /// De marks packages as explicitly installed, aka `--asexplicit`.
+    async fn de(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        let scripts = kws
+            .iter()
+            .flat_map(|kw| ["-e".into(), format!("/^{}$/d", exec::sed_escape(kw))])
+            .collect_vec();
+        Cmd::with_sudo(chain!(
+            ["sed", "-i"],
+            scripts.iter().map(String::as_str),
+            [AUTO_INSTALLED_FILE]
+        ))
+        .flags(flags)
+        .pipe(|cmd| self.run(cmd))
+        .await
+    }
+
+    /// F queries the packages from the sync sources which provide FILE.
+    async fn f(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        Cmd::new(["zypper", "search", "--provides"])
+            .kws(kws)
+            .flags(flags)
+            .pipe(|cmd| self.run(cmd))
+            .await
+    }
+
+    /// Fy refreshes the local files database.
+    async fn fy(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.check_dry(Cmd::with_sudo(["zypper", "refresh"]).flags(flags))
+            .await?;
+        if !kws.is_empty() {
+            self.f(kws, flags).await?;
+        }
+        Ok(())
+    }
+
     /// Q generates a list of installed packages.
     async fn q(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
         if kws.is_empty() {
@@ -90,6 +334,21 @@ impl Pm for Zypper {
             .await
     }
 
+    /// Qdt lists orphans, i.e. packages installed as dependencies but no
+    /// longer required by any installed package.
+    async fn qdt(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        // Each line is a table row, e.g. `i | repo-oss | curl | 8.5.0-1.1 | x86_64`.
+        Cmd::new(["zypper", "packages", "--unneeded"])
+            .flags(flags)
+            .pipe(|cmd| self.search_names(cmd, kws, |ln| ln.split('|').nth(2).map(str::trim)))
+            .await
+    }
+
+    /// Qh lists packages held back from upgrades, aka `Q --held`.
+    async fn qh(&self, _kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.run(Cmd::new(["zypper", "locks"]).flags(flags)).await
+    }
+
     /// Qi displays local package information: name, version, description, etc.
     async fn qi(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
         self.si(kws, flags).await
@@ -238,6 +497,8 @@ impl Pm for Zypper {
 
     /// Su updates outdated packages.
     async fn su(&self, _kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.hold_ignored(Cmd::with_sudo(["zypper", "addlock"]))
+            .await?;
         Cmd::with_sudo(["zypper", "--no-refresh", "dist-upgrade"])
             .flags(flags)
             .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_INSTALL))
@@ -247,6 +508,8 @@ impl Pm for Zypper {
     /// Suy refreshes the local package database, then updates outdated
     /// packages.
     async fn suy(&self, _kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.hold_ignored(Cmd::with_sudo(["zypper", "addlock"]))
+            .await?;
         Cmd::with_sudo(["zypper", "dist-upgrade"])
             .flags(flags)
             .pipe(|cmd| self.run_with(cmd, self.default_mode(), &STRAT_INSTALL))
@@ -273,6 +536,12 @@ impl Pm for Zypper {
         Ok(())
     }
 
+    /// T prints the dependencies which are not satisfied by the installed
+    /// packages.
+    async fn t(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
+        self.deptest(kws, flags).await
+    }
+
     /// U upgrades or adds package(s) to the system and installs the required
     /// dependencies from sync repositories.
     async fn u(&self, kws: &[&str], flags: &[&str]) -> Result<()> {
@@ -43,6 +43,18 @@ pub fn write_err(f: &mut fmt::Formatter, prompt: impl Display, err: impl Debug)
     )
 }
 
+/// Writes a message after the given prompt.
+#[allow(clippy::missing_errors_doc)]
+pub fn write_msg(f: &mut fmt::Formatter, prompt: impl Display, msg: impl Display) -> fmt::Result {
+    write!(
+        f,
+        plain_format!(),
+        style::MESSAGE.apply_to(prompt),
+        msg,
+        indent = PROMPT_INDENT,
+    )
+}
+
 /// Prints out a message after the given prompt.
 pub fn println(prompt: impl Display, msg: impl Display) {
     println!(
@@ -8,4 +8,5 @@ pub static CANCELED: LazyLock<StyledStr> = LazyLock::new(|| style::MESSAGE.apply
 pub static PENDING: LazyLock<StyledStr> = LazyLock::new(|| style::MESSAGE.apply_to("Pending"));
 pub static RUNNING: LazyLock<StyledStr> = LazyLock::new(|| style::MESSAGE.apply_to("Running"));
 pub static INFO: LazyLock<StyledStr> = LazyLock::new(|| style::MESSAGE.apply_to("Info"));
+pub static HINT: LazyLock<StyledStr> = LazyLock::new(|| style::MESSAGE.apply_to("Hint"));
 pub static ERROR: LazyLock<StyledStr> = LazyLock::new(|| style::ERROR.apply_to("Error"));
@@ -20,6 +20,14 @@ fn apk_q() {
     "## }
 }
 
+#[test]
+fn apk_q_json() {
+    test_dsl! { r##"
+        in --format json -Q apk-tools
+        ou "name": "apk-tools"
+    "## }
+}
+
 #[test]
 fn apk_ql() {
     test_dsl! { r##"
@@ -75,3 +83,12 @@ fn apk_ss() {
         ou wget-.*-r
     "## }
 }
+
+#[test]
+fn apk_t() {
+    test_dsl! { r##"
+        in -T 'apk-tools>=2' wget
+        ou ^wget$
+        rt 127
+    "## }
+}
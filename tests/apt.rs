@@ -3,6 +3,19 @@
 mod common;
 use common::*;
 
+#[test]
+#[ignore = "heavy test"]
+fn apt_dd_de() {
+    test_dsl! { r##"
+        in -D --asdeps curl
+        in ! apt-mark showauto curl
+        ou ^curl$
+        in -D --asexplicit curl
+        in ! apt-mark showmanual curl
+        ou ^curl$
+    "## }
+}
+
 #[test]
 #[should_panic(expected = "failed with pattern `^Package: wget$`")]
 fn apt_fail() {
@@ -12,6 +25,15 @@ fn apt_fail() {
     "## }
 }
 
+#[test]
+fn apt_fy() {
+    test_dsl! { r##"
+        in -Fy /usr/bin/fish --dry-run
+        ou apt-file update
+        ou apt-file search /usr/bin/fish
+    "## }
+}
+
 #[test]
 fn apt_q() {
     test_dsl! { r##"
@@ -20,6 +42,29 @@ fn apt_q() {
     "## }
 }
 
+#[test]
+fn apt_q_json() {
+    test_dsl! { r##"
+        in --format json -Q apt
+        ou "name": "apt"
+    "## }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn apt_q_names_only() {
+    test_dsl! { r##"
+        in -Qq
+        ou ^apt$
+        in -Qsq curl
+        ou ^curl$
+        in -Ssq ^fish$
+        ou ^fish$
+        in -Qiq apt
+        ou ^Package: apt$
+    "## }
+}
+
 #[cfg(target_os = "linux")]
 #[test]
 fn apt_qc() {
@@ -29,6 +74,28 @@ fn apt_qc() {
     "## }
 }
 
+#[test]
+fn apt_qd() {
+    test_dsl! { r##"
+        in -Qd
+        ou ^lib
+    "## }
+}
+
+#[test]
+#[ignore = "heavy test"]
+fn apt_qdt() {
+    test_dsl! { r##"
+        in -S fish --yes
+        in -D --asdeps fish
+        in -Qdt
+        ou ^fish \S+$
+        in -Qt fish
+        ou ^fish \S+$
+        in -R fish --yes
+    "## }
+}
+
 #[test]
 fn apt_qe() {
     test_dsl! { r##"
@@ -109,6 +176,98 @@ fn apt_sii() {
     "## }
 }
 
+#[test]
+fn apt_q_all() {
+    test_dsl! { r##"
+        in -Q apt --using all
+        ou ^:: apt$
+        ou ^apt \d
+    "## }
+}
+
+#[test]
+fn apt_qi_all_failed() {
+    test_dsl! { r##"
+        in ! cargo run --quiet -- -Qi pacaptr-no-such-package --using all 2>&1
+        ou ^:: apt$
+        ou failed with every package manager
+        rt 1
+    "## }
+}
+
+#[test]
+#[ignore = "heavy test"]
+fn apt_outdated_report() {
+    test_dsl! { r##"
+        in -Syu --yes
+        in --outdated-report --format json
+        ou "pm": "apt",\s+"upgrades": \[\]
+    "## }
+}
+
+#[test]
+fn apt_export() {
+    test_dsl! { r##"
+        in --export
+        ou ^pm = "apt"$
+        ou ^\[packages\]$
+        ou ^[\w.+-]+ = ">=
+    "## }
+}
+
+#[test]
+fn apt_history() {
+    test_dsl! { r##"
+        # Keep the history of the developer's own machine untouched.
+        in ! rm -rf /tmp/pacaptr-state
+        in ! XDG_STATE_HOME=/tmp/pacaptr-state cargo run --quiet -- -R wget --dry-run
+        in ! XDG_STATE_HOME=/tmp/pacaptr-state cargo run --quiet -- --history --pm apt
+        ou \A\[.+\] \[apt\] -R wget: apt remove wget \(dry run\)\n\z
+    "## }
+}
+
+#[test]
+#[ignore = "heavy test"]
+fn apt_undo() {
+    test_dsl! { r##"
+        in -S fish --yes
+        in --undo --pm apt --yes
+        ou apt remove --yes fish
+    "## }
+}
+
+#[test]
+#[ignore = "heavy test"]
+fn apt_sync_manifest() {
+    test_dsl! { r##"
+        in ! printf '[packages]\nfish = {}\n' > /tmp/pacaptr-manifest.toml
+        in sync-manifest /tmp/pacaptr-manifest.toml --yes
+        ou apt install --yes fish
+        in ! apt-mark showmanual fish
+        ou ^fish$
+    "## }
+}
+
+#[test]
+fn apt_sync_manifest_pinned() {
+    test_dsl! { r##"
+        in ! printf 'pm = "apt"\n[packages]\nfish = "3.0"\n' > /tmp/pacaptr-manifest-pinned.toml
+        in ! cargo run --quiet -- sync-manifest /tmp/pacaptr-manifest-pinned.toml --dry-run 2>&1
+        ou cannot install `fish` with version `3.0`
+        rt 1
+    "## }
+}
+
+#[test]
+fn apt_s_translate() {
+    test_dsl! { r##"
+        in -S fd python-pip --dry-run
+        ou apt install --reinstall fd-find python3-pip
+        in -S fd --dry-run --no-translate
+        ou apt install --reinstall fd`
+    "## }
+}
+
 #[test]
 fn apt_ss() {
     test_dsl! { r##"
@@ -117,3 +276,32 @@ fn apt_ss() {
         ou commandline package manager
     "## }
 }
+
+#[test]
+fn apt_su_ignore() {
+    test_dsl! { r##"
+        in -Su --ignore curl,wget --dry-run
+        ou apt-mark hold curl wget
+        ou apt upgrade
+    "## }
+}
+
+#[test]
+#[ignore = "heavy test"]
+fn apt_su_ignore_held() {
+    test_dsl! { r##"
+        in -Syu --ignore wget --yes
+        in -Q --held
+        ou ^wget$
+        in ! sudo apt-mark unhold wget
+    "## }
+}
+
+#[test]
+fn apt_t() {
+    test_dsl! { r##"
+        in -T 'apt>=2' fish
+        ou ^fish$
+        rt 127
+    "## }
+}
@@ -0,0 +1,53 @@
+#![cfg(all(target_os = "linux", feature = "test"))]
+
+mod common;
+use common::*;
+
+#[test]
+fn paru_ssa() {
+    test_dsl! { r##"
+        in --using paru -Ssa fish --dry-run
+        ou paru -Ssa fish
+    "## }
+}
+
+#[test]
+fn paru_sua() {
+    test_dsl! { r##"
+        in --using paru -Sua --dry-run
+        ou paru -Sua
+    "## }
+}
+
+#[test]
+fn yay_ssa() {
+    test_dsl! { r##"
+        in --using yay -Ssa fish --dry-run
+        ou yay -Ssa fish
+    "## }
+}
+
+#[test]
+fn yay_q() {
+    test_dsl! { r##"
+        in --using yay -Q fish --dry-run
+        ou yay -Q fish
+    "## }
+}
+
+#[test]
+fn paru_s() {
+    test_dsl! { r##"
+        in --using paru -S fish --dry-run
+        ou `paru -S fish`
+    "## }
+}
+
+#[test]
+fn pacman_ssa() {
+    test_dsl! { r##"
+        in ! cargo run --quiet -- --using pacman -Ssa fish 2>&1
+        ou `sas` is unimplemented for `pacman`
+        rt 1
+    "## }
+}
@@ -22,6 +22,14 @@ fn brew_q() {
     "## }
 }
 
+#[test]
+fn brew_q_json() {
+    test_dsl! { r##"
+        in --using brew --format json -Q wget
+        ou "name": "wget"
+    "## }
+}
+
 #[test]
 fn brew_qc() {
     test_dsl! { r##"
@@ -20,6 +20,24 @@ fn dnf_q() {
     "## }
 }
 
+#[test]
+fn dnf_q_json() {
+    test_dsl! { r##"
+        in --format json -Q dnf
+        ou "name": "dnf"
+    "## }
+}
+
+#[test]
+fn dnf_q_names_only() {
+    test_dsl! { r##"
+        in -Qq
+        ou ^dnf
+        in -Qeq
+        ou ^dnf
+    "## }
+}
+
 #[test]
 fn dnf_qc() {
     test_dsl! { r##"
@@ -135,3 +153,21 @@ fn dnf_ss() {
         ou An advanced file and recursive website downloader
     "## }
 }
+
+#[test]
+fn dnf_su_ignore() {
+    test_dsl! { r##"
+        in -Su --ignore curl,wget2 --dry-run
+        ou dnf versionlock add curl wget2
+        ou dnf upgrade
+    "## }
+}
+
+#[test]
+fn dnf_t() {
+    test_dsl! { r##"
+        in -T 'rpm>=4' wget2
+        ou ^wget2$
+        rt 127
+    "## }
+}
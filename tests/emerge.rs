@@ -75,3 +75,12 @@ fn emerge_ss() {
         ou net-misc/wget: Network utility to retrieve files from the WWW
     "## }
 }
+
+#[test]
+fn emerge_su_ignore() {
+    test_dsl! { r##"
+        in -Su --ignore curl --dry-run
+        ou >net-misc/curl-
+        ou emerge -uDN @world
+    "## }
+}
@@ -0,0 +1,102 @@
+#![cfg(all(unix, feature = "test"))]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Child, Command, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use nix::{
+    sys::signal::{Signal, kill},
+    unistd::Pid,
+};
+
+/// Spawns `pacaptr -Ss` with a package manager running `script` in `sh`, once
+/// `script` has touched the file given as `$1`.
+fn spawn_ss(test: &str, script: &str) -> (Child, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("pacaptr-test-{}-{test}", std::process::id()));
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let ready = dir.join("ready");
+    let config = dir.join("config.toml");
+    let cmd = ["sh", "-c", script, "sh", ready.to_str().unwrap()];
+    fs::write(
+        &config,
+        format!(
+            "default_pm = \"slow\"\n\n[pms.slow.ss]\ncmd = {}\n",
+            serde_json::to_string(&cmd).unwrap(),
+        ),
+    )
+    .unwrap();
+
+    let child = Command::new(env!("CARGO_BIN_EXE_pacaptr"))
+        .arg("-Ss")
+        .env("PACAPTR_CONFIG", &config)
+        // Away from a terminal, `pacaptr` forwards the interruptions by itself.
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    wait_for(&ready);
+    (child, dir)
+}
+
+/// Waits for the file at `path` to be created.
+fn wait_for(path: &Path) {
+    let start = Instant::now();
+    while !path.exists() {
+        assert!(start.elapsed() < Duration::from_secs(30), "timed out");
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Sends a `SIGINT` to `child`, as `Ctrl-C` would.
+fn interrupt(child: &Child) {
+    let pid = Pid::from_raw(child.id().try_into().unwrap());
+    kill(pid, Signal::SIGINT).unwrap();
+}
+
+/// Checks that `pacaptr` has reported the interruption of `-Ss`.
+fn assert_interrupted(out: &Output) {
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert_eq!(out.status.code(), Some(1), "{stderr}");
+    assert!(
+        stderr.contains("interrupted by signal while running `-Ss`"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn interrupt_forwarded() {
+    let (child, dir) = spawn_ss("interrupt_forwarded", r#"touch "$1"; exec sleep 60"#);
+    let start = Instant::now();
+    interrupt(&child);
+    let out = child.wait_with_output().unwrap();
+    _ = fs::remove_dir_all(dir);
+
+    assert_interrupted(&out);
+    // The command has exited on the `SIGINT` forwarded, before the grace period.
+    assert!(start.elapsed() < Duration::from_secs(4));
+}
+
+#[test]
+fn interrupt_twice() {
+    // The command ignores `SIGINT`, but not `SIGTERM`.
+    let (child, dir) = spawn_ss(
+        "interrupt_twice",
+        r#"trap '' INT; touch "$1"; exec sleep 60"#,
+    );
+    let start = Instant::now();
+    interrupt(&child);
+    thread::sleep(Duration::from_millis(200));
+    interrupt(&child);
+    let out = child.wait_with_output().unwrap();
+    _ = fs::remove_dir_all(dir);
+
+    assert_interrupted(&out);
+    // The command has been terminated right away, without a grace period.
+    assert!(start.elapsed() < Duration::from_secs(4));
+}
@@ -0,0 +1,105 @@
+#![cfg(all(target_os = "linux", feature = "test"))]
+
+mod common;
+use common::*;
+
+#[test]
+#[should_panic(expected = r"failed with pattern `^Name\s+: wget$`")]
+fn pacman_fail() {
+    test_dsl! { r##"
+        in -Si curl
+        ou ^Name\s+: wget$
+    "## }
+}
+
+#[test]
+#[ignore = "heavy test"]
+fn pacman_fy_f() {
+    test_dsl! { r##"
+        in -Fy
+        in -F /usr/bin/fish
+        ou extra/fish
+        in -Fy /usr/bin/fish
+        ou extra/fish
+    "## }
+}
+
+#[test]
+fn pacman_q() {
+    test_dsl! { r##"
+        in -Q
+        ou ^pacman
+    "## }
+}
+
+#[test]
+fn pacman_qi() {
+    test_dsl! { r##"
+        in -Qi pacman
+        ou ^Name\s+: pacman$
+    "## }
+}
+
+#[test]
+fn pacman_q_names_only() {
+    test_dsl! { r##"
+        in -Qq pacman
+        ou ^pacman$
+        in -Slq core
+        ou ^pacman$
+    "## }
+}
+
+#[test]
+fn pacman_qd() {
+    test_dsl! { r##"
+        in -Qd
+        ou ^glibc \S+$
+    "## }
+}
+
+#[test]
+fn pacman_ql() {
+    test_dsl! { r##"
+        in -Ql pacman
+        ou /usr/bin/pacman
+    "## }
+}
+
+#[test]
+#[ignore = "heavy test"]
+fn pacman_r_s() {
+    test_dsl! { r##"
+        in -S fish --yes
+        in ! which fish
+        ou /usr/bin/fish
+        in -R fish --yes
+        ou removing fish
+    "## }
+}
+
+#[test]
+fn pacman_si() {
+    test_dsl! { r##"
+        in -Si curl
+        ou ^Name\s+: curl$
+    "## }
+}
+
+#[test]
+fn pacman_ss() {
+    test_dsl! { r##"
+        in -Ss ^curl$
+        ou core/curl
+    "## }
+}
+
+#[test]
+fn pacman_su_ignore() {
+    test_dsl! { r##"
+        in -Syu --ignore linux,nvidia --ignoregroup gnome --dry-run
+        ou pacman -Suy --ignore linux,nvidia --ignoregroup gnome
+        in -Q --held --ignore linux
+        ou ^linux$
+    "## }
+}
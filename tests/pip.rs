@@ -22,6 +22,14 @@ fn pip_q() {
     "## }
 }
 
+#[test]
+fn pip_q_json() {
+    test_dsl! { r##"
+        in --using pip --format json -Q wheel
+        ou "name": "wheel"
+    "## }
+}
+
 #[test]
 fn pip_qi() {
     test_dsl! { r##"
@@ -38,6 +46,14 @@ fn pip_qs() {
     "## }
 }
 
+#[test]
+fn pip_qt() {
+    test_dsl! { r##"
+        in --using pip -Qt pip
+        ou \A\s*pip\s+\S+\s*\z
+    "## }
+}
+
 #[test]
 #[ignore = "heavy test"]
 fn pip_r_s() {